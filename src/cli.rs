@@ -0,0 +1,188 @@
+use {
+    crate::{
+        models::{ImportMode, Raffle},
+        persist::{self, SnapshotConfig},
+        routes::{open_raffle, snapshot_config},
+        shared::SharedRaffle,
+        transfer::{self, Format},
+    },
+    std::{
+        env, fs,
+        io::{self, Read, Write},
+    },
+};
+
+const USAGE: &str = "usage:
+    poppulo-rest                                                      start the server
+    poppulo-rest export [--format json|ndjson] [FILE]                 write the raffle to FILE
+    poppulo-rest import [--mode merge|replace] [--format json|ndjson] [FILE]
+                                                                      load the raffle in FILE
+
+FILE defaults to stdout / stdin, and the format to ndjson for *.ndjson files, json otherwise.
+The raffle lives in the storage Rocket.toml configures for ROCKET_ENV. With memory storage
+that is its snapshot, so stop the server before importing into it.";
+
+// Runs the subcommand named in [args], or returns None if there isn't one so the
+// server can start instead
+pub fn run(args: &[String]) -> Option<Result<(), String>> {
+    let (command, rest) = args.split_first()?;
+    Some(match command.as_str() {
+        "export" => Options::parse(rest).and_then(|options| export(&options)),
+        "import" => Options::parse(rest).and_then(|options| import(&options)),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => Err(format!("unknown command '{}'\n\n{}", other, USAGE)),
+    })
+}
+
+// Flags shared by both subcommands
+#[derive(Debug, PartialEq)]
+struct Options {
+    format: Option<Format>,
+    mode: ImportMode,
+    file: Option<String>,
+}
+
+impl Options {
+    fn parse(args: &[String]) -> Result<Self, String> {
+        let mut options = Options {
+            format: None,
+            mode: ImportMode::Merge,
+            file: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--format" => {
+                    options.format = Some(value(arg, args.next())?.parse().map_err(error)?)
+                }
+                "--mode" => options.mode = value(arg, args.next())?.parse().map_err(error)?,
+                flag if flag.starts_with("--") => {
+                    return Err(format!("unknown flag '{}'\n\n{}", flag, USAGE))
+                }
+                file if options.file.is_none() => options.file = Some(String::from(file)),
+                extra => return Err(format!("unexpected argument '{}'\n\n{}", extra, USAGE)),
+            }
+        }
+
+        Ok(options)
+    }
+
+    // The explicit format, or the one the file's extension suggests
+    fn format(&self) -> Format {
+        match (self.format, &self.file) {
+            (Some(format), _) => format,
+            (None, Some(file)) if file.ends_with(".ndjson") => Format::Ndjson,
+            (None, _) => Format::Json,
+        }
+    }
+}
+
+fn value<'a>(flag: &str, value: Option<&'a String>) -> Result<&'a String, String> {
+    value.ok_or_else(|| format!("{} needs a value\n\n{}", flag, USAGE))
+}
+
+fn export(options: &Options) -> Result<(), String> {
+    let (raffle, _) = open()?;
+    let export = raffle.export().map_err(error)?;
+    let text = transfer::encode(&export, options.format()).map_err(error)?;
+
+    match &options.file {
+        Some(file) => fs::write(file, text).map_err(error),
+        None => io::stdout().write_all(text.as_bytes()).map_err(error),
+    }
+}
+
+fn import(options: &Options) -> Result<(), String> {
+    let text = match &options.file {
+        Some(file) => fs::read_to_string(file).map_err(error)?,
+        None => {
+            let mut text = String::new();
+            io::stdin().read_to_string(&mut text).map_err(error)?;
+            text
+        }
+    };
+    let export = transfer::decode(&text, options.format()).map_err(error)?;
+
+    let (mut raffle, snapshots) = open()?;
+    let report = raffle.import(export, options.mode).map_err(error)?;
+    // Fold the import into a fresh snapshot, the server reads it on its next start
+    if let Some(snapshots) = snapshots {
+        persist::compact(&SharedRaffle::new(raffle), &snapshots.path).map_err(error)?;
+    }
+
+    println!("{}", serde_json::to_string(&report).map_err(error)?);
+    Ok(())
+}
+
+// Opens the Raffle the server would, from the storage Rocket.toml configures
+fn open() -> Result<(Raffle, Option<SnapshotConfig>), String> {
+    // Keep rocket's launch messages out of exports written to stdout
+    env::set_var("ROCKET_LOG", "off");
+    let rocket = rocket::ignite();
+    let config = rocket.config();
+
+    let mut raffle = open_raffle(config).map_err(error)?;
+    let snapshots = snapshot_config(config);
+    match &snapshots {
+        Some(snapshots) => persist::restore(&mut raffle, snapshots).map_err(error)?,
+        None if config.get_str("storage").unwrap_or("memory") == "memory" => {
+            return Err(String::from(
+                "the raffle is only kept in memory, set snapshot_path or storage in Rocket.toml",
+            ))
+        }
+        None => (),
+    }
+
+    Ok((raffle, snapshots))
+}
+
+fn error<E: std::fmt::Display>(e: E) -> String {
+    format!("{}", e)
+}
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| String::from(*arg)).collect()
+    }
+
+    #[test]
+    fn Options_parse() {
+        let options = Options::parse(&args(&["--mode", "replace", "raffle.ndjson"])).unwrap();
+        assert_eq!(options.mode, ImportMode::Replace);
+        assert_eq!(options.file, Some(String::from("raffle.ndjson")));
+        assert_eq!(options.format(), Format::Ndjson);
+
+        let options = Options::parse(&args(&["--format", "ndjson"])).unwrap();
+        assert_eq!(options.format(), Format::Ndjson);
+        assert_eq!(Options::parse(&[]).unwrap().format(), Format::Json);
+    }
+
+    #[test]
+    fn Options_parse_invalid() {
+        assert!(Options::parse(&args(&["--mode"])).is_err());
+        assert!(Options::parse(&args(&["--mode", "append"])).is_err());
+        assert!(Options::parse(&args(&["--verbose"])).is_err());
+        assert!(Options::parse(&args(&["a.json", "b.json"])).is_err());
+    }
+
+    #[test]
+    fn cli_run() {
+        assert!(run(&[]).is_none());
+        assert!(run(&args(&["help"])).unwrap().is_ok());
+        assert!(run(&args(&["frobnicate"])).unwrap().is_err());
+    }
+}
@@ -3,12 +3,31 @@
 #[macro_use]
 extern crate rocket;
 
-use crate::routes::rocket;
+use {
+    crate::routes::rocket,
+    std::{env, process},
+};
 
 // Import modules
+mod cli;
 mod models;
+mod persist;
 mod routes;
+mod shared;
+mod sweeper;
+mod transfer;
 
 fn main() {
-    rocket().launch();
+    // Admin subcommands run instead of the server
+    let args: Vec<String> = env::args().skip(1).collect();
+    match cli::run(&args) {
+        Some(Ok(())) => (),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            process::exit(1)
+        }
+        None => {
+            rocket().launch();
+        }
+    }
 }
@@ -3,108 +3,799 @@ use {
         distributions::{Distribution, Uniform},
         thread_rng as rng,
     },
-    serde::Serialize,
-    serde_json::{json, value::Value as jVal},
-    std::{collections::BTreeMap, fmt},
+    serde::{Deserialize, Deserializer, Serialize},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fmt,
+        str::FromStr,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+// Where the Raffle keeps its Tickets
+mod store;
+pub use self::store::{MemoryStore, SqliteStore, TicketStore};
+
+// Where the Raffle records Events between Snapshots
+mod journal;
+pub use self::journal::Journal;
+
+// Everything that happens to a Ticket, from which the Tickets are derived
+mod event;
+pub use self::event::{Event, EventKind, HistoryEntry, Projection, TicketHistory};
+
+// How Lines are scored
+mod rules;
+pub use self::rules::{
+    AllEqual, Always, CountAtLeast, FirstDiffers, Rule, RuleDef, RuleSet, RuleView, SumIs,
 };
 
+// The variants of the raffle served side by side
+mod game;
+pub use self::game::{Game, GameView, Games, RulesetPin, RulesetVersion, CLASSIC_GAME};
+
 // External interface object which manages the Ticket(s)
-// and ensures all Ticket(s) have a unique ID
+// and ensures all Ticket(s) have a unique ID.
+// Tickets are stored under a numeric key but known to clients by their ID, which
+// is an opaque string unless the Ticket was created under sequential IDs
 pub struct Raffle {
-    count: u64,
-    tickets: BTreeMap<u64, Ticket>,
+    store: Box<dyn TicketStore>,
+    limits: Limits,
+    journal: Option<Box<dyn Journal>>,
+    ids: IdScheme,
+    expiry: ExpiryPolicy,
+    games: Games,
 }
 
 impl Raffle {
-    // Creates the base object -- used by rocket to generate managed state
+    // Creates the base object, in memory and with the default limits
+    #[cfg(test)]
     pub fn instantiate() -> Self {
-        let count = 0u64;
-        let tickets: BTreeMap<u64, Ticket> = BTreeMap::new();
-        Raffle { count, tickets }
+        Raffle::with_limits(Limits::default())
     }
 
-    // Generates a new Ticket and returns its ID
-    pub fn new_ticket(&mut self, lines: Option<u64>) -> u64 {
-        self.count += 1;
+    // Creates the base object, bounding how many Lines a Ticket can hold
+    pub fn with_limits(limits: Limits) -> Self {
+        Raffle::with_store(Box::new(MemoryStore::new()), limits)
+    }
+
+    // Creates the base object on top of any Ticket storage backend
+    pub fn with_store(store: Box<dyn TicketStore>, limits: Limits) -> Self {
+        Raffle {
+            store,
+            limits,
+            journal: None,
+            ids: IdScheme::default(),
+            expiry: ExpiryPolicy::default(),
+            games: Games::default(),
+        }
+    }
+
+    // Serves [games] from now on, existing Tickets keep the Game they were created in
+    // and the version of its rules they were created under. Returns an error if the
+    // versions kept so far can't be read or new ones can't be stored
+    pub fn set_games(&mut self, games: Games) -> Result<(), ErrorKind> {
+        // Tickets created before the Raffle was told its Games are pinned to versions
+        // only held in memory so far, store those before they are replaced
+        let stored: BTreeSet<RulesetPin> = self
+            .store
+            .rulesets()?
+            .iter()
+            .map(RulesetVersion::pin)
+            .collect();
+        let pinned: BTreeSet<RulesetPin> = self
+            .store
+            .range(None, None)?
+            .into_iter()
+            .filter_map(|(_, ticket)| ticket.rules)
+            .collect();
+        for version in self.games.versions() {
+            if pinned.contains(&version.pin()) && !stored.contains(&version.pin()) {
+                self.add_ruleset(&version)?;
+            }
+        }
+
+        self.games = games;
+        self.keep_rulesets()
+    }
+
+    // The Game called [name], or the default Game if no name is given
+    pub fn game(&self, name: Option<&str>) -> Result<&Game, ErrorKind> {
+        self.games.get(name)
+    }
+
+    // Returns every Game served, along with its rules
+    pub fn get_games(&self) -> Vec<GameView> {
+        self.games.describe()
+    }
+
+    // Identifies every following Ticket by an ID of [scheme], existing Tickets keep theirs
+    pub fn set_id_scheme(&mut self, scheme: IdScheme) {
+        self.ids = scheme
+    }
+
+    // Decides which Tickets every following sweep expires, checks or removes
+    pub fn set_expiry(&mut self, policy: ExpiryPolicy) {
+        self.expiry = policy
+    }
 
-        if self.tickets.contains_key(&self.count) {
-            self.count = self.find_unused_key()
+    // Records every following mutation in [journal] before it is applied, starting
+    // with every version of the rules kept so far. Returns an error if they can't be
+    pub fn set_journal(&mut self, mut journal: Box<dyn Journal>) -> Result<(), ErrorKind> {
+        for version in self.games.versions() {
+            journal.record_ruleset(&version)?;
         }
+        self.journal = Some(journal);
+        Ok(())
+    }
 
+    // Generates a new Ticket in [game], or the default Game, on behalf of [requester]
+    // and returns its ID, or an error if there is no such Game or the number of Lines
+    // is outside the configured limits
+    pub fn new_ticket(
+        &mut self,
+        lines: Option<u64>,
+        game: Option<&str>,
+        requester: Option<&str>,
+    ) -> Result<String, ErrorKind> {
+        let game = self.games.get(game)?;
         // If a user provided N lines use them
-        // otherwise use default [10]
-        let ticket = match lines {
-            Some(lines) => Ticket::from(lines),
-            None => Ticket::new(),
+        // otherwise use the Game's default
+        let lines = lines.unwrap_or(game.default_lines);
+        self.limits.check(lines, 0)?;
+
+        let drawn = game.shape.draw(lines);
+        let (rules, game) = (game.pin(), game.name.clone());
+        let public_id = self.unused_id()?;
+
+        let key = self.store.allocate_id()?;
+        let created = EventKind::TicketCreated {
+            public_id,
+            game,
+            rules: Some(rules),
+            lines: drawn,
+            requester: requester.map(String::from),
         };
-
-        self.tickets.insert(self.count, ticket);
-        self.count
+        match self.emit(key, created)? {
+            Some(ticket) => Ok(ticket.id(key)),
+            None => Err(ErrorKind::TicketNotFound(key.to_string())),
+        }
     }
 
     // Returns a user defined Ticket if it exists, or an error if it doesn't
-    pub fn get_ticket(&self, id: u64) -> Result<jVal, ErrorKind> {
-        match self.tickets.get(&id) {
-            Some(ticket) => Ok(json!({"id": id, "lines": ticket.eval_list()})),
+    pub fn get_ticket(&self, id: &str) -> Result<TicketView, ErrorKind> {
+        let (key, ticket) = self.fetch(id)?;
+        Ok(TicketView::from(key, &ticket, &self.games))
+    }
+
+    // Appends N [additional] number of lines to a user defined Ticket on behalf of
+    // [requester] and returns its new line count, or an error if the ID doesn't exist,
+    // the Ticket can no longer be amended or the number of Lines is outside the configured limits
+    pub fn append_ticket(
+        &mut self,
+        id: &str,
+        additional: u64,
+        requester: Option<&str>,
+    ) -> Result<u64, ErrorKind> {
+        let (key, ticket) = self.fetch(id)?;
+        self.limits
+            .check(additional, ticket.line_list.len() as u64)?;
+
+        // Lines are always drawn in the shape of the Ticket's own Game, which has to
+        // still be served
+        let game = self.games.get(Some(&ticket.game))?;
+        let appended = EventKind::LinesAppended {
+            lines: game.shape.draw(additional),
+            requester: requester.map(String::from),
+        };
+        match self.emit(key, appended)? {
+            Some(ticket) => Ok(ticket.line_list.len() as u64),
+            None => Err(ErrorKind::TicketNotFound(String::from(id))),
+        }
+    }
+
+    // Returns the list of tickets, voided Tickets are only included if asked for
+    pub fn get_ticket_list(&self, include_voided: bool) -> Result<Vec<TicketView>, ErrorKind> {
+        Ok(self
+            .store
+            .range(None, None)?
+            .iter()
+            .filter(|(_, ticket)| include_voided || ticket.state != State::Voided)
+            .map(|(idx, ticket)| TicketView::from(*idx, ticket, &self.games))
+            .collect::<Vec<TicketView>>())
+    }
+
+    // Returns one page of the Tickets matching [query] in the order it asks for, along
+    // with a cursor to the next page and how many Tickets matched in total,
+    // or an error if the query's cursor is malformed or its Ticket is gone
+    pub fn query_tickets(&self, query: &TicketQuery) -> Result<TicketPage, ErrorKind> {
+        let after = match &query.after {
+            Some(cursor) => {
+                let (value, id) = parse_cursor(cursor, query.sort)?;
+                let key = match self.fetch(id) {
+                    Ok((key, _)) => key,
+                    Err(ErrorKind::TicketNotFound(_)) => {
+                        return Err(ErrorKind::InvalidQuery(format!(
+                            "cursor '{}' points at a ticket that no longer exists",
+                            cursor
+                        )))
+                    }
+                    Err(e) => return Err(e),
+                };
+                Some((value.unwrap_or(key), key))
+            }
+            None => None,
+        };
+
+        // Tickets listed in ID order, and only filtered by state, are read a page at a
+        // time. Anything else needs every Ticket to sort or filter them
+        let limit = query.limit.max(1).min(MAX_PAGE_SIZE) as usize;
+        let (mut page, total) = match query.sort {
+            SortKey::Id if !query.descending && query.by_state_only() => (
+                self.range_page(query, after.map(|(_, id)| id), limit)?,
+                self.store.count_in(&query.states())?,
+            ),
+            _ => self.scan_page(query, after, limit)?,
+        };
+
+        // Only hand out a cursor if there is something after this page
+        let next = match page.len() > limit {
+            true => {
+                page.truncate(limit);
+                page.last().map(|((value, key), ticket)| match query.sort {
+                    SortKey::Id => ticket.id(*key),
+                    _ => format!("{}.{}", value, ticket.id(*key)),
+                })
+            }
+            false => None,
+        };
+
+        Ok(TicketPage {
+            tickets: page
+                .iter()
+                .map(|((_, id), ticket)| TicketView::from(*id, ticket, &self.games))
+                .collect(),
+            next,
+            total,
+        })
+    }
+
+    // Checks a Ticket, locking it against further amendment, and returns a rough
+    // estimate of how lucky the user was, or returns an error if the ID doesn't exist.
+    // Checking an already checked Ticket returns the result stored the first time
+    pub fn evaluate_ticket(&mut self, id: &str) -> Result<StatusResult, ErrorKind> {
+        let (key, ticket) = self.fetch(id)?;
+        match &ticket.result {
+            Some(result) => Ok(result.clone()),
             None => {
-                let err = Err(ErrorKind::TicketNotFound(id));
-                err
+                self.games.get(Some(&ticket.game))?;
+                self.check(key, ticket)
             }
         }
     }
 
-    // Appends N [additional] number of lines to a user defined Ticket,
-    // or returns an error if the ID doesn't exist
-    pub fn append_ticket(&mut self, id: u64, additional: u64) -> Result<(), ErrorKind> {
-        match self.tickets.get_mut(&id) {
-            Some(ticket) => Ok(ticket.append(additional)),
-            None => {
-                let err = Err(ErrorKind::TicketNotFound(id));
-                err
+    // Cancels a Ticket without removing it, so it stays around for auditing,
+    // or returns an error if the ID doesn't exist or the Ticket was already checked.
+    // Voiding an already voided Ticket is a no-op
+    pub fn void_ticket(&mut self, id: &str) -> Result<TicketView, ErrorKind> {
+        let (key, ticket) = self.fetch(id)?;
+        let ticket = match ticket.state {
+            State::Voided => Some(ticket),
+            _ => self.emit(key, EventKind::TicketVoided)?,
+        };
+
+        match ticket {
+            Some(ticket) => Ok(TicketView::from(key, &ticket, &self.games)),
+            None => Err(ErrorKind::TicketNotFound(String::from(id))),
+        }
+    }
+
+    // Returns a Ticket's current state and every transition it went through,
+    // or an error if the ID doesn't exist
+    pub fn get_transitions(&self, id: &str) -> Result<TransitionLog, ErrorKind> {
+        let (key, ticket) = self.fetch(id)?;
+        Ok(TransitionLog {
+            id: ticket.id(key),
+            state: ticket.state,
+            transitions: ticket.transitions,
+        })
+    }
+
+    // Applies the expiry policy to every Ticket: open Tickets past their TTL are
+    // expired or checked, and checked or expired Tickets past their retention period
+    // are removed. Returns how many Tickets were swept, or an error if storage fails
+    pub fn sweep(&mut self) -> Result<SweepReport, ErrorKind> {
+        self.sweep_at(now())
+    }
+
+    // Returns every Event that happened to a Ticket, oldest first,
+    // or an error if the ID doesn't exist
+    pub fn get_history(&self, id: &str) -> Result<TicketHistory, ErrorKind> {
+        let (key, ticket) = self.fetch(id)?;
+        Ok(TicketHistory {
+            id: ticket.id(key),
+            events: self
+                .store
+                .events(Some(key))?
+                .into_iter()
+                .map(HistoryEntry::from)
+                .collect(),
+        })
+    }
+
+    // Feeds every Event, oldest first, to [projection] so new read models can be built
+    // from everything that ever happened. Returns how many Events it was fed
+    pub fn project(&self, projection: &mut dyn Projection) -> Result<u64, ErrorKind> {
+        let events = self.store.events(None)?;
+        for event in &events {
+            projection.apply(event)?;
+        }
+        Ok(events.len() as u64)
+    }
+
+    // Throws every stored Ticket away and derives them again from the Events,
+    // returning how many Tickets there are afterwards
+    pub fn rebuild(&mut self) -> Result<u64, ErrorKind> {
+        let mut tickets = BTreeMap::new();
+        for event in self.store.events(None)? {
+            if let Some(ticket) = event.apply(tickets.remove(&event.ticket), &self.games)? {
+                tickets.insert(event.ticket, ticket);
+            }
+        }
+
+        for (key, _) in self.store.range(None, None)? {
+            self.store.remove(key)?;
+        }
+        for (key, ticket) in tickets {
+            self.store.insert(key, ticket)?;
+        }
+        self.prune_rulesets()?;
+        self.store.len()
+    }
+
+    // Records a TicketImported Event for every Ticket stored before Events were
+    // recorded, so the Events account for all of them. Returns how many it found
+    pub fn adopt_untracked(&mut self) -> Result<u64, ErrorKind> {
+        let tracked: BTreeSet<u64> = self
+            .store
+            .events(None)?
+            .iter()
+            .map(|event| event.ticket)
+            .collect();
+
+        let mut adopted = 0;
+        for (key, ticket) in self.store.range(None, None)? {
+            if !tracked.contains(&key) {
+                self.emit(key, EventKind::TicketImported { ticket })?;
+                adopted += 1;
             }
         }
+        Ok(adopted)
+    }
+
+    // Captures every Ticket, the Events they came from, the versions of the rules
+    // they are scored by and the ID counter, so the Raffle can be rebuilt later
+    pub fn snapshot(&self) -> Result<Snapshot, ErrorKind> {
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            last_id: self.store.last_id()?,
+            tickets: self.store.range(None, None)?,
+            events: self.store.events(None)?,
+            last_seq: self.store.last_seq()?,
+            rulesets: self.games.versions(),
+        })
     }
 
-    // Returns the entire list of tickets as Json
-    pub fn get_ticket_list(&self) -> jVal {
-        let json: jVal = self
+    // Loads every Ticket, Event and version of the rules from a snapshot, replacing
+    // any Ticket or version stored under the same ID and skipping Events already
+    // recorded, and moves the ID and Event counters past every ID and Event the
+    // snapshot had handed out. Nothing is loaded if a Line doesn't fit its Game
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), ErrorKind> {
+        let problems: Vec<String> = snapshot
             .tickets
             .iter()
-            .map(|(idx, ticket)| {
-                json!({
-                    "id": idx,
-                    "lines": ticket.eval_list()
-                })
-            })
+            .flat_map(|(id, ticket)| self.misshapen(*id, ticket))
+            .collect();
+        if !problems.is_empty() {
+            return Err(ErrorKind::Storage(format!(
+                "unusable snapshot: {}",
+                problems.join("; ")
+            )));
+        }
+
+        let last_id = self.store.last_id()?.max(snapshot.last_id);
+        for version in &snapshot.rulesets {
+            self.add_ruleset(version)?;
+        }
+        for (id, ticket) in snapshot.tickets {
+            match self.store.get(id)? {
+                Some(_) => self.store.update(id, ticket)?,
+                None => self.store.insert(id, ticket)?,
+            }
+        }
+        let last_seq = self.store.last_seq()?;
+        for event in snapshot.events {
+            if event.seq > last_seq {
+                self.store.append_event(&event)?;
+            }
+        }
+        self.store
+            .set_last_seq(self.store.last_seq()?.max(snapshot.last_seq))?;
+
+        self.store.set_last_id(last_id)?;
+        self.keep_rulesets()
+    }
+
+    // Captures every Ticket, the versions of the rules they are scored by, the ID
+    // counter and when it was taken, so the Raffle can be moved to another environment
+    pub fn export(&self) -> Result<Export, ErrorKind> {
+        let tickets: Vec<ExportedTicket> = self
+            .store
+            .range(None, None)?
+            .into_iter()
+            .map(|(id, ticket)| ExportedTicket { id, ticket })
             .collect();
 
-        json
+        Ok(Export {
+            version: EXPORT_VERSION,
+            exported_at: now(),
+            last_id: self.store.last_id()?,
+            ticket_count: tickets.len() as u64,
+            tickets,
+            rulesets: self.games.versions(),
+        })
     }
 
-    // Uses up a Ticket and returns a rough estimate of how lucky the user was,
-    // or returns an error if the ID doesn't exist
-    pub fn evaluate_ticket(&mut self, id: u64) -> Result<jVal, ErrorKind> {
-        match self.tickets.remove(&id) {
-            Some(ticket) => {
-                let list = ticket.eval_list();
-                let sum: u64 = list.iter().map(|i| *i as u64).sum();
-                let score = sum / list.len() as u64;
-                Ok(Raffle::generate_response(id, score))
+    // Loads every Ticket of an export once all of them are validated, or returns an
+    // error listing every problem found. Merging keeps the existing Tickets and skips,
+    // and reports the key of, any imported one whose key or ID is taken; replacing
+    // removes them first.
+    // Versions of the rules the export brings along are kept as the version with the
+    // same rules here, or as a new one, and its Tickets are pinned to them.
+    // Either way the ID counter never moves backwards, so IDs are never reused
+    pub fn import(&mut self, export: Export, mode: ImportMode) -> Result<ImportReport, ErrorKind> {
+        export.validate(&self.games)?;
+
+        let mut pins = BTreeMap::new();
+        for version in export.rulesets {
+            let pinned = version.pin();
+            let (pin, added) = self.games.adopt(version);
+            if let Some(added) = added {
+                self.add_ruleset(&added)?;
+            }
+            pins.insert(pinned, pin);
+        }
+
+        let existing = self.store.range(None, None)?;
+        let mut collisions = BTreeSet::new();
+        match mode {
+            ImportMode::Merge => {
+                let taken: BTreeSet<u64> = existing.iter().map(|(id, _)| *id).collect();
+                for ExportedTicket { id, ticket } in &export.tickets {
+                    let public_id_taken = match &ticket.public_id {
+                        Some(public_id) => self.store.find(public_id)?.is_some(),
+                        None => false,
+                    };
+                    if taken.contains(id) || public_id_taken {
+                        collisions.insert(*id);
+                    }
+                }
+            }
+            ImportMode::Replace => {
+                for (id, _) in existing {
+                    self.emit(id, EventKind::TicketRemoved)?;
+                }
+            }
+        }
+
+        let mut last_id = self.store.last_id()?.max(export.last_id);
+        let mut imported = 0;
+        for ExportedTicket { id, mut ticket } in export.tickets {
+            if !collisions.contains(&id) {
+                ticket.rules = ticket.rules.and_then(|pin| pins.get(&pin).cloned());
+                self.emit(id, EventKind::TicketImported { ticket })?;
+                last_id = last_id.max(id);
+                imported += 1;
             }
-            None => Err(ErrorKind::TicketNotFound(id)),
         }
+        self.store.set_last_id(last_id)?;
+        self.prune_rulesets()?;
+
+        Ok(ImportReport {
+            mode,
+            imported,
+            collisions: collisions.into_iter().collect(),
+            last_id,
+        })
     }
 
-    // Internal function for finding the next unique ID
-    fn find_unused_key(&self) -> u64 {
-        (self.count..)
-            .filter(|k| !self.tickets.contains_key(k))
-            .take(1)
-            .sum()
+    // Applies a journaled Event on top of the current state, without journaling it
+    // again, and moves the ID counter past its Ticket's ID. Events already recorded,
+    // because a Snapshot covers them, are skipped, and one leaving a Line that doesn't
+    // fit its Game is refused
+    pub fn replay(&mut self, event: Event) -> Result<(), ErrorKind> {
+        if event.seq <= self.store.last_seq()? {
+            return Ok(());
+        }
+        let last_id = self.store.last_id()?.max(event.ticket);
+        let after = event.apply(self.store.get(event.ticket)?, &self.games)?;
+        if let Some(ticket) = &after {
+            let problems = self.misshapen(event.ticket, ticket);
+            if !problems.is_empty() {
+                return Err(ErrorKind::Storage(format!(
+                    "unusable journal event {}: {}",
+                    event.seq,
+                    problems.join("; ")
+                )));
+            }
+        }
+
+        self.store.commit(&event, after)?;
+        self.store.set_last_id(last_id)
+    }
+
+    // Takes over a journaled version of the rules, without journaling it again, and
+    // numbers each Game's current rules against it. It is kept even though no Ticket
+    // is pinned to it yet, as the Events that follow it in the journal may be.
+    // Returns an error if a Ticket is already pinned to a different version under
+    // the same number
+    pub fn replay_ruleset(&mut self, version: RulesetVersion) -> Result<(), ErrorKind> {
+        self.add_ruleset(&version)?;
+        self.number_rulesets()
+    }
+
+    // Drops every journaled Event, only call this once a Snapshot covers them
+    pub fn truncate_journal(&mut self) -> Result<(), ErrorKind> {
+        match &mut self.journal {
+            Some(journal) => journal.truncate(),
+            None => Ok(()),
+        }
+    }
+
+    // Internal function for sweeping as if it was [at] seconds since the unix epoch
+    fn sweep_at(&mut self, at: u64) -> Result<SweepReport, ErrorKind> {
+        let policy = self.expiry;
+        let elapsed = |since: u64, limit: Option<u64>| match limit {
+            Some(limit) => at.saturating_sub(since) >= limit,
+            None => false,
+        };
+
+        let mut report = SweepReport::default();
+        for (key, ticket) in self.store.range(None, None)? {
+            match ticket.state {
+                State::Open | State::Amended if elapsed(ticket.created_at(), policy.open_ttl) => {
+                    match policy.on_ttl {
+                        TtlAction::Expire => {
+                            self.emit(key, EventKind::TicketExpired)?;
+                            report.expired += 1
+                        }
+                        TtlAction::Check => {
+                            self.check(key, ticket)?;
+                            report.checked += 1
+                        }
+                    }
+                }
+                State::Checked | State::Expired
+                    if elapsed(ticket.updated_at(), policy.retention) =>
+                {
+                    self.emit(key, EventKind::TicketRemoved)?;
+                    report.removed += 1
+                }
+                _ => (),
+            }
+        }
+        if report.removed > 0 {
+            self.prune_rulesets()?;
+        }
+
+        Ok(report)
+    }
+
+    // Internal function for numbering the rules of every Game served against the
+    // versions stored so far, storing any new version and dropping unused ones
+    fn keep_rulesets(&mut self) -> Result<(), ErrorKind> {
+        self.number_rulesets()?;
+        self.prune_rulesets()
+    }
+
+    // Internal function for numbering the rules of every Game served against the
+    // versions stored so far, storing any new version
+    fn number_rulesets(&mut self) -> Result<(), ErrorKind> {
+        for version in self.games.keep(self.store.rulesets()?) {
+            self.add_ruleset(&version)?;
+        }
+        Ok(())
+    }
+
+    // Internal function for storing [version] of a Game's rules, journaled first so it
+    // is durable before any Ticket is pinned to it. A version some Ticket is pinned to
+    // is never redefined, one nothing is pinned to yet is replaced
+    fn add_ruleset(&mut self, version: &RulesetVersion) -> Result<(), ErrorKind> {
+        let pin = version.pin();
+        let redefined = self
+            .store
+            .rulesets()?
+            .into_iter()
+            .chain(self.games.versions())
+            .any(|kept| kept.pin() == pin && kept.rules != version.rules);
+        if redefined && self.referenced_rulesets()?.contains(&pin) {
+            return Err(ErrorKind::RulesetConflict(pin));
+        }
+
+        if let Some(journal) = &mut self.journal {
+            journal.record_ruleset(version)?;
+        }
+        self.store.save_ruleset(version)
+    }
+
+    // Internal function for dropping every version of the rules no Ticket is pinned
+    // to and no Game is using anymore
+    fn prune_rulesets(&mut self) -> Result<(), ErrorKind> {
+        let referenced = self.referenced_rulesets()?;
+        for pin in self.games.prune(&referenced) {
+            self.store.remove_ruleset(&pin)?;
+        }
+        Ok(())
+    }
+
+    // Internal function for finding every version of the rules a stored Ticket is scored by
+    fn referenced_rulesets(&self) -> Result<BTreeSet<RulesetPin>, ErrorKind> {
+        Ok(self
+            .store
+            .range(None, None)?
+            .iter()
+            .filter_map(|(_, ticket)| self.games.pin_of(ticket))
+            .collect())
+    }
+
+    // Internal function for explaining which Lines of [ticket], stored under [id], don't
+    // fit the shape of its Game. Tickets of a Game no longer served can't be checked
+    fn misshapen(&self, id: u64, ticket: &Ticket) -> Vec<String> {
+        match self.games.find(&ticket.game) {
+            Some(game) => ticket
+                .misshapen_lines(&game.shape)
+                .iter()
+                .map(|problem| format!("ticket {} {}", id, problem))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Internal function for checking an unchecked Ticket, storing and returning its result
+    fn check(&mut self, key: u64, ticket: Ticket) -> Result<StatusResult, ErrorKind> {
+        let list = ticket.eval_list(self.games.rules_of(&ticket));
+        let sum: u64 = list.iter().map(|i| *i as u64).sum();
+        let score = sum / (list.len() as u64).max(1);
+        let checked = EventKind::TicketChecked {
+            score,
+            message: Raffle::generate_response(&ticket.id(key), score),
+        };
+
+        match self.emit(key, checked)?.and_then(|ticket| ticket.result) {
+            Some(result) => Ok(result),
+            None => Err(ErrorKind::TicketNotFound(ticket.id(key))),
+        }
+    }
+
+    // Internal function for recording that [kind] happened to the Ticket stored under
+    // [key]. The Event is staged by applying it to a copy of the Ticket, so one that
+    // can't happen is never recorded, then journaled so it is never acknowledged without
+    // being durable, and finally committed together with the Ticket it left behind,
+    // taking it back out of the journal if that fails.
+    // Returns the Ticket as it is afterwards, None if it was removed
+    fn emit(&mut self, key: u64, kind: EventKind) -> Result<Option<Ticket>, ErrorKind> {
+        let event = Event {
+            seq: self.store.last_seq()? + 1,
+            ticket: key,
+            at: now(),
+            kind,
+        };
+        let after = event.apply(self.store.get(key)?, &self.games)?;
+
+        if let Some(journal) = &mut self.journal {
+            journal.record(&event)?;
+        }
+        if let Err(e) = self.store.commit(&event, after.clone()) {
+            if let Some(journal) = &mut self.journal {
+                journal.rollback()?;
+            }
+            return Err(e);
+        }
+        Ok(after)
+    }
+
+    // Internal function for reading up to [limit] + 1 Tickets matching [query] in ID
+    // order, starting after the ID [after], a page's worth of Tickets at a time
+    fn range_page(
+        &self,
+        query: &TicketQuery,
+        mut after: Option<u64>,
+        limit: usize,
+    ) -> Result<KeyedPage, ErrorKind> {
+        let mut page = Vec::new();
+        loop {
+            let chunk = self.store.range(after, Some(limit + 1))?;
+            let exhausted = chunk.len() <= limit;
+            for (id, ticket) in chunk {
+                after = Some(id);
+                if query.matches(&ticket, &self.games) {
+                    page.push(((id, id), ticket));
+                }
+                if page.len() > limit {
+                    return Ok(page);
+                }
+            }
+            if exhausted {
+                return Ok(page);
+            }
+        }
+    }
+
+    // Internal function for sorting every Ticket matching [query], returning up to
+    // [limit] + 1 of them past the cursor [after] along with how many matched
+    fn scan_page(
+        &self,
+        query: &TicketQuery,
+        after: Option<(u64, u64)>,
+        limit: usize,
+    ) -> Result<(KeyedPage, u64), ErrorKind> {
+        // Every matching Ticket keyed by its sort value, ties broken by id
+        let mut matched: KeyedPage = self
+            .store
+            .range(None, None)?
+            .into_iter()
+            .filter(|(_, ticket)| query.matches(ticket, &self.games))
+            .map(|(id, ticket)| ((query.sort.key(id, &ticket, &self.games), id), ticket))
+            .collect();
+        matched.sort_by_key(|(key, _)| *key);
+        if query.descending {
+            matched.reverse()
+        }
+
+        let total = matched.len() as u64;
+        let page = matched
+            .into_iter()
+            .filter(|(key, _)| match after {
+                Some(cursor) if query.descending => *key < cursor,
+                Some(cursor) => *key > cursor,
+                None => true,
+            })
+            .take(limit + 1)
+            .collect();
+        Ok((page, total))
+    }
+
+    // Internal function for loading a Ticket that must exist, along with its key.
+    // Only Tickets without an opaque ID can be found by their key
+    fn fetch(&self, id: &str) -> Result<(u64, Ticket), ErrorKind> {
+        let key = match self.store.find(id)? {
+            Some(key) => Some(key),
+            None => id.parse::<u64>().ok().filter(|key| key.to_string() == id),
+        };
+        let ticket = match key {
+            Some(key) => self.store.get(key)?.map(|ticket| (key, ticket)),
+            None => None,
+        };
+
+        match ticket {
+            Some((key, ticket)) if ticket.id(key) == id => Ok((key, ticket)),
+            _ => Err(ErrorKind::TicketNotFound(String::from(id))),
+        }
+    }
+
+    // Internal function for generating an opaque ID no stored Ticket is using,
+    // or None if Tickets are known by their key
+    fn unused_id(&self) -> Result<Option<String>, ErrorKind> {
+        loop {
+            match self.ids.generate() {
+                Some(id) if self.store.find(&id)?.is_some() => continue,
+                id => return Ok(id),
+            }
+        }
     }
 
     // Generate random responses from a group of pools
-    fn generate_response(id: u64, score: u64) -> jVal {
+    fn generate_response(id: &str, score: u64) -> String {
         let (mut s, r) = (rng(), Uniform::from(0..3));
         let rand = r.sample(&mut s);
         let response = match score {
@@ -114,372 +805,2863 @@ impl Raffle {
             _n @ 8..=9 => GREAT[rand],
             _ => JACKPOT[rand],
         };
-        json!(format!(
+        format!(
             "For ticket {}, your score was {}... {}",
             id, score, response
-        ))
+        )
     }
 }
 
-// Internal representation of a Ticket
-#[derive(Clone, Serialize, Debug, PartialEq)]
-struct Ticket {
-    line_list: Vec<Line>,
+// The complete state of a Raffle, as written to and read back from disk
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    // Highest ID handed out so far, IDs are never reused even if a Ticket is removed
+    pub last_id: u64,
+    tickets: Vec<(u64, Ticket)>,
+    // Every Event of the Tickets still stored, Snapshots from before they were
+    // recorded have none
+    #[serde(default)]
+    events: Vec<Event>,
+    // Sequence number of the last Event, which may belong to a Ticket since removed
+    #[serde(default)]
+    last_seq: u64,
+    // Every version of the rules kept, Snapshots from before they were versioned have none
+    #[serde(default)]
+    rulesets: Vec<RulesetVersion>,
 }
 
-impl Ticket {
-    // Creates a Ticket with the default number of Lines [10]
-    // uses thread-specific system entropy for its RNG
-    fn new() -> Self {
-        let line_list = (0..10)
-            .scan((rng(), Uniform::from(0..3)), |(s, r), _| {
-                Some((r.sample(s), r.sample(s), r.sample(s)))
-            })
-            .map(|rand| Line::from(rand))
-            .collect::<Vec<Line>>();
+// Version of the Snapshot format, bumped whenever its shape changes
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+// The complete state of a Raffle in a form that can move between environments,
+// unlike a Snapshot it is meant to be read, and written, by other tools
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Export {
+    pub version: u32,
+    // Unix seconds at which the export was taken
+    pub exported_at: u64,
+    pub last_id: u64,
+    pub ticket_count: u64,
+    pub tickets: Vec<ExportedTicket>,
+    // Versions of the rules the Tickets are pinned to, exports from before they were
+    // versioned have none
+    #[serde(default)]
+    pub rulesets: Vec<RulesetVersion>,
+}
+
+impl Export {
+    // Checks the export is one this build understands and that every Ticket in it
+    // could have been produced by a Raffle serving [games], pinned to a version of
+    // the rules the export brings along
+    fn validate(&self, games: &Games) -> Result<(), ErrorKind> {
+        let mut problems = Vec::new();
+        if self.version != EXPORT_VERSION {
+            problems.push(format!(
+                "unsupported export version {}, expected {}",
+                self.version, EXPORT_VERSION
+            ));
+        }
+        if self.ticket_count != self.tickets.len() as u64 {
+            problems.push(format!(
+                "ticket_count is {} but {} tickets are present",
+                self.ticket_count,
+                self.tickets.len()
+            ));
+        }
+
+        let mut versions = BTreeSet::new();
+        for version in &self.rulesets {
+            if !versions.insert(version.pin()) {
+                problems.push(format!(
+                    "ruleset {} version {} appears more than once",
+                    version.id, version.version
+                ));
+            }
+        }
+
+        let (mut seen, mut public_ids) = (BTreeSet::new(), BTreeSet::new());
+        for ExportedTicket { id, ticket } in &self.tickets {
+            if *id == 0 {
+                problems.push(String::from("ticket id 0 is reserved"));
+            }
+            if !seen.insert(*id) {
+                problems.push(format!("ticket {} appears more than once", id));
+            }
+            match games.find(&ticket.game) {
+                Some(game) => problems.extend(
+                    ticket
+                        .misshapen_lines(&game.shape)
+                        .iter()
+                        .map(|problem| format!("ticket {} {}", id, problem)),
+                ),
+                None => problems.push(format!(
+                    "ticket {} belongs to unknown game '{}'",
+                    id, ticket.game
+                )),
+            }
+            match &ticket.rules {
+                Some(pin) if !versions.contains(pin) => problems.push(format!(
+                    "ticket {} is pinned to ruleset {} version {} which isn't exported",
+                    id, pin.id, pin.version
+                )),
+                _ => (),
+            }
+            if ticket.transitions.last().map(|t| t.to) != Some(ticket.state) {
+                problems.push(format!(
+                    "ticket {} is {} but its transitions don't end there",
+                    id, ticket.state
+                ));
+            }
+            let covered = ticket.batches.iter().try_fold(0, |start, batch| {
+                match batch.start == start && batch.end >= start {
+                    true => Some(batch.end),
+                    false => None,
+                }
+            });
+            match covered {
+                _ if ticket.batches.is_empty() => (),
+                Some(end) if end == ticket.line_list.len() as u64 => (),
+                _ => problems.push(format!(
+                    "ticket {} has amendment batches that don't cover its lines in order",
+                    id
+                )),
+            }
+            if let Some(public_id) = &ticket.public_id {
+                if !public_ids.insert(public_id) {
+                    problems.push(format!("ticket id {} appears more than once", public_id));
+                }
+            }
+            match &ticket.result {
+                Some(result) if result.id != ticket.id(*id) => problems.push(format!(
+                    "ticket {} holds the check result of ticket {}",
+                    id, result.id
+                )),
+                None if ticket.state == State::Checked => {
+                    problems.push(format!("ticket {} is checked but has no result", id))
+                }
+                _ => (),
+            }
+        }
 
-        Ticket { line_list }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(ErrorKind::InvalidImport(problems)),
+        }
     }
+}
 
-    // Creates a Ticket with a custom number of Lines [lines]
-    // uses thread-specific system entropy for its RNG
-    fn from(lines: u64) -> Self {
-        let line_list = (0..lines)
-            .scan((rng(), Uniform::from(0..3)), |(s, r), _| {
-                Some((r.sample(s), r.sample(s), r.sample(s)))
-            })
-            .map(|seed| Line::from(seed))
-            .collect::<Vec<Line>>();
+// A single Ticket of an Export, with its raw Lines, state and transitions
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExportedTicket {
+    pub id: u64,
+    #[serde(flatten)]
+    ticket: Ticket,
+}
+
+// Version of the Export format, bumped whenever its shape changes
+pub const EXPORT_VERSION: u32 = 1;
+
+// How an import treats the Tickets already in the Raffle
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportMode {
+    Merge,
+    Replace,
+}
+
+impl FromStr for ImportMode {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "merge" => Ok(ImportMode::Merge),
+            "replace" => Ok(ImportMode::Replace),
+            other => Err(ErrorKind::InvalidQuery(format!(
+                "unknown import mode '{}', expected one of: merge, replace",
+                other
+            ))),
+        }
+    }
+}
+
+// What an import did, [collisions] are the IDs a merge skipped because they were taken
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ImportReport {
+    pub mode: ImportMode,
+    pub imported: u64,
+    pub collisions: Vec<u64>,
+    pub last_id: u64,
+}
+
+// How new Tickets are identified to clients
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IdScheme {
+    // The Ticket's key: 1, 2, 3... readable, but anyone can guess the next one
+    Sequential,
+    // Lexically sortable by creation time, 80 of its 128 bits are random
+    Ulid,
+    // Random (v4) UUID
+    Uuid,
+    // 128 random bits in unpadded, lowercase base32
+    Base32,
+}
+
+impl IdScheme {
+    // A fresh ID, or None if Tickets are known by their key
+    fn generate(self) -> Option<String> {
+        match self {
+            IdScheme::Sequential => None,
+            IdScheme::Ulid => Some(ulid::Ulid::new().to_string()),
+            IdScheme::Uuid => Some(uuid::Uuid::new_v4().to_string()),
+            IdScheme::Base32 => {
+                let bits: [u8; 16] = rand::random();
+                Some(data_encoding::BASE32_NOPAD.encode(&bits).to_lowercase())
+            }
+        }
+    }
+}
+
+impl Default for IdScheme {
+    fn default() -> Self {
+        IdScheme::Sequential
+    }
+}
+
+impl FromStr for IdScheme {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "sequential" => Ok(IdScheme::Sequential),
+            "ulid" => Ok(IdScheme::Ulid),
+            "uuid" => Ok(IdScheme::Uuid),
+            "base32" => Ok(IdScheme::Base32),
+            other => Err(ErrorKind::InvalidConfig(format!(
+                "unknown id scheme '{}', expected one of: sequential, ulid, uuid, base32",
+                other
+            ))),
+        }
+    }
+}
+
+// Tickets from before there were games belong to the classic game
+fn classic_game() -> String {
+    String::from(CLASSIC_GAME)
+}
+
+// IDs used to be numbers, so accept those too when reading stored results back
+fn string_or_number<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Id {
+        Text(String),
+        Number(u64),
+    }
+
+    Ok(match Id::deserialize(deserializer)? {
+        Id::Text(id) => id,
+        Id::Number(id) => id.to_string(),
+    })
+}
+
+// When the sweeper deals with Tickets, in seconds. Open and amended Tickets are
+// expired or checked, depending on [on_ttl], once [open_ttl] has passed since they
+// were created. Checked and expired Tickets are removed once [retention] has passed
+// since they got there. Voided Tickets are kept for auditing, and None means never
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExpiryPolicy {
+    pub open_ttl: Option<u64>,
+    pub on_ttl: TtlAction,
+    pub retention: Option<u64>,
+}
+
+impl ExpiryPolicy {
+    // Whether sweeping under this policy could ever change a Ticket
+    pub fn sweeps(&self) -> bool {
+        self.open_ttl.is_some() || self.retention.is_some()
+    }
+}
+
+impl Default for ExpiryPolicy {
+    fn default() -> Self {
+        ExpiryPolicy {
+            open_ttl: None,
+            on_ttl: TtlAction::Expire,
+            retention: None,
+        }
+    }
+}
+
+// What happens to an open Ticket once its TTL has passed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TtlAction {
+    Expire,
+    Check,
+}
+
+impl FromStr for TtlAction {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "expire" => Ok(TtlAction::Expire),
+            "check" => Ok(TtlAction::Check),
+            other => Err(ErrorKind::InvalidConfig(format!(
+                "unknown expiry action '{}', expected one of: expire, check",
+                other
+            ))),
+        }
+    }
+}
+
+// How many Tickets a single sweep expired, checked and removed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct SweepReport {
+    pub expired: u64,
+    pub checked: u64,
+    pub removed: u64,
+}
+
+impl SweepReport {
+    pub fn total(&self) -> u64 {
+        self.expired + self.checked + self.removed
+    }
+}
+
+impl fmt::Display for SweepReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} expired, {} checked, {} removed",
+            self.expired, self.checked, self.removed
+        )
+    }
+}
+
+// Bounds on how many Lines can be requested at once and held by a single Ticket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Limits {
+    pub min_per_request: u64,
+    pub max_per_request: u64,
+    pub max_per_ticket: u64,
+}
+
+impl Limits {
+    // Checks that N [requested] Lines can be added to a Ticket already holding [held] Lines
+    fn check(&self, requested: u64, held: u64) -> Result<(), ErrorKind> {
+        let max = self
+            .max_per_request
+            .min(self.max_per_ticket.saturating_sub(held));
+        match requested {
+            n if n >= self.min_per_request && n <= max => Ok(()),
+            n => Err(ErrorKind::InvalidLineCount(n, self.min_per_request, max)),
+        }
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            min_per_request: 1,
+            max_per_request: 1_000,
+            max_per_ticket: 10_000,
+        }
+    }
+}
+
+// A Ticket as returned to clients
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TicketView {
+    pub id: String,
+    pub game: String,
+    // Version of the Game's rules the Ticket is scored by
+    pub rules: Option<RulesetPin>,
+    pub state: State,
+    pub checked: bool,
+    pub line_count: u64,
+    pub total_score: u64,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub checked_at: Option<u64>,
+    batches: Vec<Batch>,
+    lines: Vec<TicketLine>,
+}
+
+impl TicketView {
+    fn from(key: u64, ticket: &Ticket, games: &Games) -> Self {
+        let rules = games.rules_of(ticket);
+        TicketView {
+            id: ticket.id(key),
+            game: ticket.game.clone(),
+            rules: games.pin_of(ticket),
+            state: ticket.state,
+            checked: ticket.state == State::Checked,
+            line_count: ticket.line_list.len() as u64,
+            total_score: ticket.total_score(rules),
+            created_at: ticket.created_at(),
+            updated_at: ticket.updated_at(),
+            checked_at: ticket.checked_at(),
+            batches: ticket.batches.clone(),
+            lines: ticket.lines(rules),
+        }
+    }
+}
+
+// A single page of Tickets, [next] is the cursor to pass to get the following page
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TicketPage {
+    pub tickets: Vec<TicketView>,
+    pub next: Option<String>,
+    pub total: u64,
+}
+
+// Tickets on a page being listed, each keyed by its sort value and ID
+type KeyedPage = Vec<((u64, u64), Ticket)>;
+
+// Which Tickets to list and in what order, see Raffle::query_tickets
+#[derive(Debug, Clone, PartialEq)]
+pub struct TicketQuery {
+    // Cursor returned as [next] by the previous page
+    pub after: Option<String>,
+    pub limit: u64,
+    // Only list Tickets in this state, overrides [include_voided]
+    pub state: Option<State>,
+    pub include_voided: bool,
+    pub min_lines: Option<u64>,
+    pub min_score: Option<u64>,
+    pub max_score: Option<u64>,
+    pub sort: SortKey,
+    pub descending: bool,
+}
+
+impl TicketQuery {
+    fn matches(&self, ticket: &Ticket, games: &Games) -> bool {
+        let state = match self.state {
+            Some(state) => ticket.state == state,
+            None => self.include_voided || ticket.state != State::Voided,
+        };
+        // Scoring a Ticket is only worth it if the score decides whether it matches
+        let scored = || match (self.min_score, self.max_score) {
+            (None, None) => true,
+            (min, max) => {
+                let score = ticket.total_score(games.rules_of(ticket));
+                min.map_or(true, |n| score >= n) && max.map_or(true, |n| score <= n)
+            }
+        };
+
+        state
+            && self
+                .min_lines
+                .map_or(true, |n| ticket.line_list.len() as u64 >= n)
+            && scored()
+    }
+
+    // Whether a Ticket matches on its state alone
+    fn by_state_only(&self) -> bool {
+        self.min_lines.is_none() && self.min_score.is_none() && self.max_score.is_none()
+    }
+
+    // The states a matching Ticket can be in
+    fn states(&self) -> Vec<State> {
+        match self.state {
+            Some(state) => vec![state],
+            None => [
+                State::Open,
+                State::Amended,
+                State::Checked,
+                State::Voided,
+                State::Expired,
+            ]
+            .iter()
+            .cloned()
+            .filter(|state| self.include_voided || *state != State::Voided)
+            .collect(),
+        }
+    }
+}
+
+impl Default for TicketQuery {
+    fn default() -> Self {
+        TicketQuery {
+            after: None,
+            limit: DEFAULT_PAGE_SIZE,
+            state: None,
+            include_voided: false,
+            min_lines: None,
+            min_score: None,
+            max_score: None,
+            sort: SortKey::Id,
+            descending: false,
+        }
+    }
+}
+
+// What a Ticket listing can be ordered by
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Id,
+    Lines,
+    Score,
+}
+
+impl SortKey {
+    fn key(self, id: u64, ticket: &Ticket, games: &Games) -> u64 {
+        match self {
+            SortKey::Id => id,
+            SortKey::Lines => ticket.line_list.len() as u64,
+            SortKey::Score => ticket.total_score(games.rules_of(ticket)),
+        }
+    }
+}
+
+impl FromStr for SortKey {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "id" => Ok(SortKey::Id),
+            "lines" => Ok(SortKey::Lines),
+            "score" => Ok(SortKey::Score),
+            other => Err(ErrorKind::InvalidQuery(format!(
+                "unknown sort key '{}', expected one of: id, lines, score",
+                other
+            ))),
+        }
+    }
+}
+
+// Cursors are the ID of the last Ticket on a page, preceded by its sort value and
+// a '.' unless listing by ID. No ID scheme hands out IDs containing a '.'
+fn parse_cursor(cursor: &str, sort: SortKey) -> Result<(Option<u64>, &str), ErrorKind> {
+    let invalid = || ErrorKind::InvalidQuery(format!("malformed cursor '{}'", cursor));
+    if sort == SortKey::Id {
+        return match cursor.is_empty() {
+            true => Err(invalid()),
+            false => Ok((None, cursor)),
+        };
+    }
+
+    let mut parts = cursor.splitn(2, '.');
+    match (parts.next().map(str::parse::<u64>), parts.next()) {
+        (Some(Ok(value)), Some(id)) if !id.is_empty() => Ok((Some(value), id)),
+        _ => Err(invalid()),
+    }
+}
+
+// The outcome of checking a Ticket, [lines] are grouped by outcome, best first
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusResult {
+    #[serde(deserialize_with = "string_or_number")]
+    pub id: String,
+    pub score: u64,
+    pub message: String,
+    lines: Vec<Outcome>,
+}
+
+// A Ticket's current State and how it got there
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransitionLog {
+    pub id: String,
+    pub state: State,
+    transitions: Vec<Transition>,
+}
+
+// Internal representation of a Ticket
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Ticket {
+    // Opaque ID clients know the Ticket by, None if it is known by its key instead
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    public_id: Option<String>,
+    // Name of the Game the Ticket was created in, see Games::find
+    #[serde(default = "classic_game")]
+    game: String,
+    // Version of the Game's rules the Ticket is scored by, see Games::pin_of.
+    // Tickets from before rules were versioned have none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rules: Option<RulesetPin>,
+    line_list: Vec<Line>,
+    // Where the Ticket is in its lifecycle, see State
+    state: State,
+    // Every state the Ticket has entered, oldest first
+    transitions: Vec<Transition>,
+    // The status check response, kept so repeated checks agree
+    result: Option<StatusResult>,
+    // Where every Line came from, oldest first. Tickets from before these
+    // were recorded have none
+    #[serde(default)]
+    batches: Vec<Batch>,
+}
+
+impl Ticket {
+    // Explains which of the Lines couldn't have been drawn in [shape], if any
+    fn misshapen_lines(&self, shape: &LineShape) -> Vec<String> {
+        self.line_list
+            .iter()
+            .enumerate()
+            .filter_map(|(index, line)| {
+                let problem = shape.check(line).err()?;
+                Some(format!("line {} {}", index, problem))
+            })
+            .collect()
+    }
+
+    // Wraps a list of Lines in a fresh, open Ticket
+    fn with_lines(line_list: Vec<Line>) -> Self {
+        Ticket::created(line_list, None, now())
+    }
+
+    // Wraps a list of Lines in an open Ticket created [at] on behalf of [requester]
+    fn created(line_list: Vec<Line>, requester: Option<String>, at: u64) -> Self {
+        Ticket {
+            public_id: None,
+            game: classic_game(),
+            rules: None,
+            batches: vec![Batch {
+                at,
+                start: 0,
+                end: line_list.len() as u64,
+                requester,
+            }],
+            line_list,
+            state: State::Open,
+            transitions: vec![Transition::to(None, State::Open, at)],
+            result: None,
+        }
+    }
+
+    // The ID clients know the Ticket stored under [key] by
+    fn id(&self, key: u64) -> String {
+        match &self.public_id {
+            Some(id) => id.clone(),
+            None => key.to_string(),
+        }
+    }
+
+    // When the Ticket was created, in seconds since the unix epoch
+    fn created_at(&self) -> u64 {
+        self.transitions.first().map_or(0, |t| t.at)
+    }
+
+    // When the Ticket last changed, which is when it entered its current state,
+    // in seconds since the unix epoch
+    fn updated_at(&self) -> u64 {
+        self.transitions.last().map_or(0, |t| t.at)
+    }
+
+    // When the Ticket was checked, in seconds since the unix epoch, None if it wasn't
+    fn checked_at(&self) -> Option<u64> {
+        self.transitions
+            .iter()
+            .find(|t| t.to == State::Checked)
+            .map(|t| t.at)
+    }
+
+    // Moves the Ticket [id] into a new state, recording that it happened [at],
+    // or returns an error if the lifecycle doesn't allow it
+    fn transition(&mut self, id: &str, to: State, at: u64) -> Result<(), ErrorKind> {
+        match (self.state, to) {
+            (State::Open, State::Amended)
+            | (State::Amended, State::Amended)
+            | (State::Open, State::Checked)
+            | (State::Amended, State::Checked)
+            | (State::Open, State::Voided)
+            | (State::Amended, State::Voided)
+            | (State::Open, State::Expired)
+            | (State::Amended, State::Expired) => {
+                self.transitions
+                    .push(Transition::to(Some(self.state), to, at));
+                self.state = to;
+                Ok(())
+            }
+            (State::Checked, _) => Err(ErrorKind::TicketAlreadyChecked(String::from(id))),
+            (State::Voided, _) => Err(ErrorKind::TicketVoided(String::from(id))),
+            (State::Expired, _) => Err(ErrorKind::TicketExpired(String::from(id))),
+            (from, to) => Err(ErrorKind::IllegalTransition(String::from(id), from, to)),
+        }
+    }
+
+    // Appends [lines] to an existing ticket, added [at] on behalf of [requester]
+    fn append(&mut self, lines: Vec<Line>, requester: Option<String>, at: u64) {
+        let start = self.line_list.len() as u64;
+        self.line_list.extend(lines);
+        self.batches.push(Batch {
+            at,
+            start,
+            end: self.line_list.len() as u64,
+            requester,
+        })
+    }
+
+    // Computes the output for all Lines in a Ticket
+    fn eval_list(&self, rules: &RuleSet) -> Vec<u8> {
+        self.line_list
+            .iter()
+            .map(|line| line.eval_line(rules))
+            .collect::<Vec<u8>>()
+    }
+
+    // Sums the output of every Line in a Ticket
+    fn total_score(&self, rules: &RuleSet) -> u64 {
+        self.line_list
+            .iter()
+            .map(|line| line.eval_line(rules) as u64)
+            .sum()
+    }
+
+    // Pairs each Line's raw numbers with its output and position in the Ticket
+    fn lines(&self, rules: &RuleSet) -> Vec<TicketLine> {
+        self.line_list
+            .iter()
+            .enumerate()
+            .map(|(index, line)| TicketLine::from(index, line.clone(), rules))
+            .collect::<Vec<TicketLine>>()
+    }
+
+    // Sorts the Ticket's Lines into their outcomes, best outcome first
+    fn outcomes(&self, rules: &RuleSet) -> Vec<Outcome> {
+        let mut groups: Vec<Outcome> = rules.outcomes().into_iter().map(Outcome::empty).collect();
+        self.line_list.iter().enumerate().for_each(|(index, line)| {
+            let line = TicketLine::from(index, line.clone(), rules);
+            if let Some(group) = groups.iter_mut().find(|g| g.outcome == line.result) {
+                group.push(line)
+            }
+        });
+
+        groups
+    }
+}
+
+// A group of Lines from a Ticket which share the same outcome
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Outcome {
+    outcome: u8,
+    count: u64,
+    subtotal: u64,
+    lines: Vec<TicketLine>,
+}
+
+impl Outcome {
+    fn empty(outcome: u8) -> Self {
+        Outcome {
+            outcome,
+            count: 0,
+            subtotal: 0,
+            lines: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, line: TicketLine) {
+        self.count += 1;
+        self.subtotal += self.outcome as u64;
+        self.lines.push(line)
+    }
+}
+
+// A Line's raw numbers and output, alongside its position in its Ticket
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct TicketLine {
+    index: usize,
+    numbers: Line,
+    result: u8,
+}
+
+impl TicketLine {
+    fn from(index: usize, numbers: Line, rules: &RuleSet) -> Self {
+        TicketLine {
+            index,
+            result: numbers.eval_line(rules),
+            numbers,
+        }
+    }
+}
+
+impl fmt::Display for Ticket {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[")?;
+        for (i, line) in self.line_list.iter().enumerate() {
+            if i != 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{}", line)?;
+        }
+        write!(f, "]")
+    }
+}
+
+// The lifecycle of a Ticket: it starts Open, becomes Amended whenever lines
+// are appended and ends up in exactly one of the terminal states
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum State {
+    Open,
+    Amended,
+    Checked,
+    Voided,
+    Expired,
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            State::Open => write!(f, "open"),
+            State::Amended => write!(f, "amended"),
+            State::Checked => write!(f, "checked"),
+            State::Voided => write!(f, "voided"),
+            State::Expired => write!(f, "expired"),
+        }
+    }
+}
+
+impl FromStr for State {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "open" => Ok(State::Open),
+            "amended" => Ok(State::Amended),
+            "checked" => Ok(State::Checked),
+            "voided" => Ok(State::Voided),
+            "expired" => Ok(State::Expired),
+            other => Err(ErrorKind::InvalidQuery(format!(
+                "unknown state '{}', expected one of: open, amended, checked, voided, expired",
+                other
+            ))),
+        }
+    }
+}
+
+// Lines added to a Ticket in one go, when it was created or amended: Lines [start]
+// up to but not including [end], added [at] seconds since the unix epoch
+// on behalf of [requester], if anyone said who they were
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Batch {
+    at: u64,
+    start: u64,
+    end: u64,
+    requester: Option<String>,
+}
+
+// A single recorded change of a Ticket's State, [at] is in seconds since the unix epoch
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+struct Transition {
+    from: Option<State>,
+    to: State,
+    at: u64,
+}
+
+impl Transition {
+    fn to(from: Option<State>, to: State, at: u64) -> Self {
+        Transition { from, to, at }
+    }
+}
+
+// Current time in seconds since the unix epoch
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+// Named tuple which holds as many numbers as its LineShape is wide, by default 3
+// numbers between 0 and 2: [0,1,2]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Line(Vec<u8>);
+
+impl Line {
+    // The Line's numbers, in order
+    pub fn numbers(&self) -> &[u8] {
+        &self.0
+    }
+
+    // Computes a Line's output based on the given rules
+    fn eval_line(&self, rules: &RuleSet) -> u8 {
+        rules.evaluate(self)
+    }
+}
+
+// Shows what the Line is worth in the classic game
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "|{}|", self.eval_line(&RuleSet::default()))
+    }
+}
+
+// How many numbers a Line holds and how many symbols each can be, numbers run
+// from 0 to [symbols] - 1
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LineShape {
+    pub width: usize,
+    pub symbols: u8,
+}
+
+impl LineShape {
+    // A shape of [width] numbers out of [symbols], or an error if no Line fits it
+    pub fn new(width: usize, symbols: u8) -> Result<Self, ErrorKind> {
+        match (width, symbols) {
+            (1..=MAX_LINE_WIDTH, 1..=255) => Ok(LineShape { width, symbols }),
+            _ => Err(ErrorKind::InvalidShape(format!(
+                "lines must be 1 to {} numbers wide out of 1 to 255 symbols, not {} out of {}",
+                MAX_LINE_WIDTH, width, symbols
+            ))),
+        }
+    }
+
+    // Draws [count] random Lines,
+    // uses thread-specific system entropy for its RNG
+    fn draw(&self, count: u64) -> Vec<Line> {
+        let (mut s, r) = (rng(), Uniform::from(0..self.symbols));
+        (0..count)
+            .map(|_| Line((0..self.width).map(|_| r.sample(&mut s)).collect()))
+            .collect::<Vec<Line>>()
+    }
+
+    // Explains why [line] couldn't have been drawn in this shape, if it couldn't
+    fn check(&self, line: &Line) -> Result<(), String> {
+        if line.0.len() != self.width {
+            return Err(format!(
+                "has {} numbers, lines must have {}",
+                line.0.len(),
+                self.width
+            ));
+        }
+        match line.0.iter().find(|n| **n >= self.symbols) {
+            Some(n) => Err(format!(
+                "holds {}, values must be within 0..={}",
+                n,
+                self.symbols - 1
+            )),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Default for LineShape {
+    // The classic game, 3 numbers between 0 and 2
+    fn default() -> Self {
+        LineShape {
+            width: 3,
+            symbols: 3,
+        }
+    }
+}
+
+// Widest Line a LineShape can describe
+pub const MAX_LINE_WIDTH: usize = 16;
+
+// Number of Lines a Ticket of the classic game is created with unless the user asks otherwise
+pub const DEFAULT_LINES: u64 = 10;
+
+// Number of Tickets listed per page unless the user asks otherwise, and the most they can ask for
+pub const DEFAULT_PAGE_SIZE: u64 = 50;
+pub const MAX_PAGE_SIZE: u64 = 500;
+
+// Error kind(s) used by Raffle
+#[derive(Debug, Clone)]
+pub enum ErrorKind {
+    TicketNotFound(String),
+    TicketAlreadyChecked(String),
+    TicketVoided(String),
+    TicketExpired(String),
+    IllegalTransition(String, State, State),
+    InvalidLineCount(u64, u64, u64),
+    InvalidQuery(String),
+    InvalidConfig(String),
+    Storage(String),
+    InvalidImport(Vec<String>),
+    InvalidRules(Vec<String>),
+    InvalidShape(String),
+    InvalidGames(Vec<String>),
+    UnknownGame(String),
+    RulesetConflict(RulesetPin),
+}
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorKind::TicketNotFound(id) => write!(f, "Ticket id: {} doesn't exist", id),
+            ErrorKind::TicketAlreadyChecked(id) => {
+                write!(f, "Ticket id: {} has already been checked", id)
+            }
+            ErrorKind::TicketVoided(id) => write!(f, "Ticket id: {} has been voided", id),
+            ErrorKind::TicketExpired(id) => write!(f, "Ticket id: {} has expired", id),
+            ErrorKind::IllegalTransition(id, from, to) => {
+                write!(f, "Ticket id: {} can't go from {} to {}", id, from, to)
+            }
+            ErrorKind::InvalidLineCount(requested, min, max) => write!(
+                f,
+                "Line count: {} must be between {} and {}",
+                requested, min, max
+            ),
+            ErrorKind::InvalidQuery(reason) => write!(f, "Invalid query: {}", reason),
+            ErrorKind::InvalidConfig(reason) => write!(f, "Invalid configuration: {}", reason),
+            ErrorKind::Storage(reason) => write!(f, "Storage failure: {}", reason),
+            ErrorKind::InvalidImport(problems) => {
+                write!(f, "Invalid import: {}", problems.join("; "))
+            }
+            ErrorKind::InvalidRules(problems) => {
+                write!(f, "Invalid rules: {}", problems.join("; "))
+            }
+            ErrorKind::InvalidShape(reason) => write!(f, "Invalid line shape: {}", reason),
+            ErrorKind::InvalidGames(problems) => {
+                write!(f, "Invalid games: {}", problems.join("; "))
+            }
+            ErrorKind::UnknownGame(name) => write!(f, "No game called '{}'", name),
+            ErrorKind::RulesetConflict(pin) => write!(
+                f,
+                "Ruleset '{}' version {} is already kept with different rules",
+                pin.id, pin.version
+            ),
+        }
+    }
+}
+
+const TERRIBLE: [&str; 3] = [
+    "you get nothing; good day sir!",
+    "I award you no points, and may...",
+    "I bet you have terrible anxiety!",
+];
+const MEDIOCRE: [&str; 3] = [
+    "slightly better than a hostel shower!",
+    "confidence is your prize!",
+    "its lonely being stuck in the middle!",
+];
+const AVERAGE: [&str; 3] = [
+    "you're one of today's lucky 10,000!",
+    "mostly better than half even...",
+    "delta Vee / delta Tee",
+];
+const GREAT: [&str; 3] = [
+    "almost enough for a cheese pizza!",
+    "thinking like a queen!",
+    "heralding from simplicity, goodness and truth",
+];
+const JACKPOT: [&str; 3] = [
+    "ding ding ding, you won the imaginary jackpot!",
+    "winner winner, chicken dinner!",
+    "supercalifragilisticexpialidocious",
+];
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+    use serde_json::{json, value::Value as jVal};
+
+    impl Line {
+        // A Line of the classic game
+        pub(in crate::models) fn from((x, y, z): (u8, u8, u8)) -> Self {
+            Line(vec![x, y, z])
+        }
+    }
+
+    impl Ticket {
+        // A Ticket of [lines] random Lines of the classic game
+        pub(in crate::models) fn from(lines: u64) -> Self {
+            Ticket::with_lines(LineShape::default().draw(lines))
+        }
+    }
+
+    macro_rules! static_ticket {
+        () => {{
+            let line_list = LINE_SEED_VALUES
+                .iter()
+                .map(|i| Line::from(*i))
+                .collect::<Vec<Line>>();
+            let ticket = Ticket::with_lines(line_list);
+            ticket
+        }};
+    }
+
+    // ErrorKind tests
+    #[test]
+    fn TicketNotFound_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::TicketNotFound(String::from("42"))),
+            "Ticket id: 42 doesn't exist"
+        )
+    }
+
+    #[test]
+    fn TicketAlreadyChecked_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::TicketAlreadyChecked(String::from("42"))),
+            "Ticket id: 42 has already been checked"
+        )
+    }
+
+    #[test]
+    fn TicketVoided_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::TicketVoided(String::from("42"))),
+            "Ticket id: 42 has been voided"
+        )
+    }
+
+    #[test]
+    fn TicketExpired_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::TicketExpired(String::from("42"))),
+            "Ticket id: 42 has expired"
+        )
+    }
+
+    #[test]
+    fn IllegalTransition_display() {
+        assert_eq!(
+            format!(
+                "{}",
+                ErrorKind::IllegalTransition(String::from("42"), State::Amended, State::Open)
+            ),
+            "Ticket id: 42 can't go from amended to open"
+        )
+    }
+
+    #[test]
+    fn InvalidLineCount_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::InvalidLineCount(0, 1, 1000)),
+            "Line count: 0 must be between 1 and 1000"
+        )
+    }
+
+    #[test]
+    fn InvalidQuery_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::InvalidQuery(String::from("bad cursor"))),
+            "Invalid query: bad cursor"
+        )
+    }
+
+    #[test]
+    fn InvalidImport_display() {
+        assert_eq!(
+            format!(
+                "{}",
+                ErrorKind::InvalidImport(vec![String::from("a"), String::from("b")])
+            ),
+            "Invalid import: a; b"
+        )
+    }
+
+    #[test]
+    fn InvalidRules_display() {
+        assert_eq!(
+            format!(
+                "{}",
+                ErrorKind::InvalidRules(vec![String::from("a"), String::from("b")])
+            ),
+            "Invalid rules: a; b"
+        )
+    }
+
+    #[test]
+    fn UnknownGame_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::UnknownGame(String::from("bingo"))),
+            "No game called 'bingo'"
+        )
+    }
+
+    #[test]
+    fn InvalidConfig_display() {
+        assert_eq!(
+            format!(
+                "{}",
+                ErrorKind::InvalidConfig(String::from("unknown id scheme"))
+            ),
+            "Invalid configuration: unknown id scheme"
+        )
+    }
+
+    #[test]
+    fn InvalidShape_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::InvalidShape(String::from("too wide"))),
+            "Invalid line shape: too wide"
+        )
+    }
+
+    #[test]
+    fn Storage_display() {
+        assert_eq!(
+            format!("{}", ErrorKind::Storage(String::from("disk full"))),
+            "Storage failure: disk full"
+        )
+    }
+
+    // Line tests
+
+    #[test]
+    fn Line_serialize() {
+        let line = Line::from(LINE_SEED_VALUES[0]);
+        assert_eq!(serde_json::to_value(&line).unwrap(), json!([1, 0, 2]));
+        assert_eq!(
+            serde_json::from_value::<Line>(json!([1, 0, 2])).unwrap(),
+            line
+        );
+        assert_eq!(
+            serde_json::from_value::<Line>(json!([4, 3, 2, 1, 0])).unwrap(),
+            Line(vec![4, 3, 2, 1, 0])
+        );
+    }
+
+    #[test]
+    fn Line_eval_line() {
+        LINE_SEED_VALUES
+            .iter()
+            .zip(LINE_EVAL_VALUES.iter())
+            .map(|(seed, value)| (Line::from(*seed).eval_line(&RuleSet::default()), *value))
+            .for_each(|(eval, value)| assert_eq!(eval, value))
+    }
+
+    #[test]
+    fn Line_display() {
+        let line = Line::from(LINE_SEED_VALUES[0]);
+        let evaluated = LINE_EVAL_VALUES[0];
+
+        assert_eq!(format!("{}", line), format!("|{}|", evaluated));
+    }
+
+    // LineShape tests
+
+    #[test]
+    fn LineShape_new() {
+        assert_eq!(LineShape::new(3, 3).unwrap(), LineShape::default());
+        assert!(LineShape::new(MAX_LINE_WIDTH, 255).is_ok());
+        assert!(LineShape::new(0, 3).is_err());
+        assert!(LineShape::new(MAX_LINE_WIDTH + 1, 3).is_err());
+        match LineShape::new(5, 0) {
+            Err(ErrorKind::InvalidShape(reason)) => assert!(reason.ends_with("not 5 out of 0")),
+            other => panic!("expected InvalidShape, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn LineShape_draw() {
+        let drawn = LineShape::default().draw(DEFAULT_LINES);
+        // It is theoretically possible for this test to fail due the inherent randomness;
+        // therefore check against a third rand generation, before failing the test
+        assert!(
+            drawn != LineShape::default().draw(DEFAULT_LINES)
+                || drawn != LineShape::default().draw(DEFAULT_LINES)
+        );
+
+        let shape = LineShape::new(5, 5).unwrap();
+        let drawn = shape.draw(100);
+        assert_eq!(drawn.len(), 100);
+        assert!(drawn.iter().all(|line| shape.check(line).is_ok()));
+        // Every symbol of the alphabet turns up eventually
+        assert!((0..5).all(|n| drawn.iter().any(|line| line.numbers().contains(&n))));
+    }
+
+    #[test]
+    fn LineShape_check() {
+        let shape = LineShape::new(4, 5).unwrap();
+        assert!(shape.check(&Line(vec![0, 1, 4, 4])).is_ok());
+        assert_eq!(
+            shape.check(&Line(vec![0, 1, 4])),
+            Err(String::from("has 3 numbers, lines must have 4"))
+        );
+        assert_eq!(
+            shape.check(&Line(vec![0, 5, 1, 9])),
+            Err(String::from("holds 5, values must be within 0..=4"))
+        );
+    }
+
+    // Ticket tests
+
+    #[test]
+    fn Ticket_new() {
+        let new = || Ticket::with_lines(LineShape::default().draw(DEFAULT_LINES));
+        let ticket = new();
+        let ticket2 = new();
+        assert_eq!(ticket.line_list.len(), DEFAULT_LINES as usize);
+        // It is theoretically possible for this test to fail due the inherent randomness;
+        // therefore check against a third rand generation, before failing the test
+        assert!(ticket != ticket2 || ticket != new())
+    }
+
+    #[test]
+    fn Ticket_from() {
+        (1..=10).for_each(|lines_num| {
+            assert_eq!(Ticket::from(lines_num).line_list.len(), lines_num as usize)
+        })
+    }
+
+    #[test]
+    fn Ticket_append() {
+        let start_size = 10;
+        let additional_lines = 7;
+        let end_size = 17;
+        assert_eq!(start_size + additional_lines, end_size);
+
+        let mut ticket = Ticket::from(start_size);
+        ticket.append(Ticket::from(additional_lines).line_list, None, now());
+        assert_eq!(ticket.line_list.len(), end_size as usize);
+    }
+
+    #[test]
+    fn Ticket_eval_list() {
+        let line_list: Vec<Line> = LINE_SEED_VALUES.iter().map(|i| Line::from(*i)).collect();
+        let ticket = Ticket::with_lines(line_list);
+        assert_eq!(
+            ticket.eval_list(&RuleSet::default()),
+            LINE_EVAL_VALUES.iter().map(|i| *i).collect::<Vec<u8>>()
+        );
+    }
+    #[test]
+    fn Ticket_outcomes() {
+        let ticket = static_ticket!();
+        let outcomes = ticket.outcomes(&RuleSet::default());
+
+        assert_eq!(
+            outcomes.iter().map(|o| o.outcome).collect::<Vec<u8>>(),
+            vec![10, 5, 1, 0]
+        );
+        outcomes.iter().for_each(|group| {
+            let expected: Vec<usize> = LINE_EVAL_VALUES
+                .iter()
+                .enumerate()
+                .filter(|(_, value)| **value == group.outcome)
+                .map(|(index, _)| index)
+                .collect();
+            assert_eq!(
+                group.lines.iter().map(|l| l.index).collect::<Vec<usize>>(),
+                expected
+            );
+            assert_eq!(group.count, expected.len() as u64);
+            assert_eq!(group.subtotal, group.count * group.outcome as u64);
+            group
+                .lines
+                .iter()
+                .for_each(|l| assert_eq!(l.numbers, Line::from(LINE_SEED_VALUES[l.index])));
+        });
+    }
+
+    #[test]
+    fn Ticket_transition_legal() {
+        let mut ticket = static_ticket!();
+        assert!(ticket.transition("1", State::Amended, now()).is_ok());
+        assert!(ticket.transition("1", State::Amended, now()).is_ok());
+        assert!(ticket.transition("1", State::Checked, now()).is_ok());
+
+        assert_eq!(ticket.state, State::Checked);
+        assert_eq!(
+            ticket
+                .transitions
+                .iter()
+                .map(|t| (t.from, t.to))
+                .collect::<Vec<(Option<State>, State)>>(),
+            vec![
+                (None, State::Open),
+                (Some(State::Open), State::Amended),
+                (Some(State::Amended), State::Amended),
+                (Some(State::Amended), State::Checked),
+            ]
+        );
+    }
+
+    #[test]
+    fn Ticket_transition_illegal() {
+        let mut ticket = static_ticket!();
+        ticket.transition("1", State::Amended, now()).unwrap();
+        match ticket.transition("1", State::Open, now()) {
+            Err(ErrorKind::IllegalTransition(ref id, State::Amended, State::Open)) if id == "1" => {
+                ()
+            }
+            other => panic!("expected IllegalTransition, got {:?}", other),
+        }
+
+        [
+            (State::Checked, "TicketAlreadyChecked"),
+            (State::Voided, "TicketVoided"),
+            (State::Expired, "TicketExpired"),
+        ]
+        .iter()
+        .for_each(|(terminal, kind)| {
+            let mut ticket = static_ticket!();
+            ticket.transition("1", *terminal, now()).unwrap();
+            let recorded = ticket.transitions.len();
+            let err = ticket.transition("1", State::Amended, now()).unwrap_err();
+
+            assert!(format!("{:?}", err).starts_with(kind));
+            assert_eq!(ticket.state, *terminal);
+            assert_eq!(ticket.transitions.len(), recorded);
+        });
+    }
+
+    #[test]
+    fn Ticket_display() {
+        let line_list: Vec<Line> = LINE_SEED_VALUES
+            .iter()
+            .take(10)
+            .map(|i| Line::from(*i))
+            .collect();
+        let ticket = Ticket::with_lines(line_list);
+        let evals: Vec<u8> = LINE_EVAL_VALUES.iter().take(10).map(|i| *i).collect();
+        assert_eq!(ticket.line_list.len(), evals.len());
+
+        assert_eq!(
+            format!("{}", ticket),
+            format!(
+                "[|{}| |{}| |{}| |{}| |{}| |{}| |{}| |{}| |{}| |{}|]",
+                evals[0],
+                evals[1],
+                evals[2],
+                evals[3],
+                evals[4],
+                evals[5],
+                evals[6],
+                evals[7],
+                evals[8],
+                evals[9],
+            )
+        )
+    }
+
+    // Limits tests
+    #[test]
+    fn Limits_check() {
+        let limits = Limits {
+            min_per_request: 1,
+            max_per_request: 100,
+            max_per_ticket: 150,
+        };
+
+        assert!(limits.check(1, 0).is_ok());
+        assert!(limits.check(100, 0).is_ok());
+        assert!(limits.check(50, 100).is_ok());
+        match limits.check(0, 0) {
+            Err(ErrorKind::InvalidLineCount(0, 1, 100)) => (),
+            other => panic!("expected InvalidLineCount, got {:?}", other),
+        }
+        match limits.check(101, 0) {
+            Err(ErrorKind::InvalidLineCount(101, 1, 100)) => (),
+            other => panic!("expected InvalidLineCount, got {:?}", other),
+        }
+        match limits.check(51, 100) {
+            Err(ErrorKind::InvalidLineCount(51, 1, 50)) => (),
+            other => panic!("expected InvalidLineCount, got {:?}", other),
+        }
+        assert!(limits.check(1, 200).is_err());
+    }
+
+    // TicketQuery tests
+    #[test]
+    fn SortKey_from_str() {
+        assert_eq!("id".parse::<SortKey>().unwrap(), SortKey::Id);
+        assert_eq!("lines".parse::<SortKey>().unwrap(), SortKey::Lines);
+        assert_eq!("score".parse::<SortKey>().unwrap(), SortKey::Score);
+        assert!("color".parse::<SortKey>().is_err());
+    }
+
+    #[test]
+    fn State_from_str() {
+        [
+            State::Open,
+            State::Amended,
+            State::Checked,
+            State::Voided,
+            State::Expired,
+        ]
+        .iter()
+        .for_each(|state| assert_eq!(format!("{}", state).parse::<State>().unwrap(), *state));
+        assert!("lost".parse::<State>().is_err());
+    }
+
+    #[test]
+    fn parse_cursor_formats() {
+        assert_eq!(parse_cursor("7", SortKey::Id).unwrap(), (None, "7"));
+        assert_eq!(
+            parse_cursor("01E2XN5T8Q", SortKey::Id).unwrap(),
+            (None, "01E2XN5T8Q")
+        );
+        assert_eq!(
+            parse_cursor("12.7", SortKey::Score).unwrap(),
+            (Some(12), "7")
+        );
+        assert!(parse_cursor("", SortKey::Id).is_err());
+        assert!(parse_cursor("7", SortKey::Lines).is_err());
+        assert!(parse_cursor("a.7", SortKey::Score).is_err());
+        assert!(parse_cursor("12.", SortKey::Score).is_err());
+    }
+
+    // Raffle tests
+    #[test]
+    fn Raffle_instantiate() {
+        let base_raffle = Raffle::instantiate();
+
+        assert_eq!(base_raffle.store.len().unwrap(), 0);
+    }
+
+    #[test]
+    fn Raffle_new_ticket_default() {
+        let mut raffle = Raffle::instantiate();
+        let ticket_id = raffle.new_ticket(None, None, None).unwrap();
+
+        assert!(raffle.store.len().unwrap() == 1 && ticket_id == "1")
+    }
+
+    #[test]
+    fn Raffle_new_ticket_with_lines() {
+        let mut raffle = Raffle::instantiate();
+        let len80 = 80;
+        let ticket_id = raffle.new_ticket(Some(len80), None, None).unwrap();
+
+        let stored = raffle.store.range(None, None).unwrap();
+
+        assert!(
+            stored.len() == 1
+                && ticket_id == stored[0].0.to_string()
+                && len80 as usize == stored[0].1.line_list.len()
+        )
+    }
+
+    #[test]
+    fn Raffle_new_ticket_limits() {
+        let mut raffle = Raffle::with_limits(Limits {
+            min_per_request: 1,
+            max_per_request: 20,
+            max_per_ticket: 25,
+        });
+
+        assert!(raffle.new_ticket(Some(0), None, None).is_err());
+        assert!(raffle.new_ticket(Some(21), None, None).is_err());
+        assert_eq!(raffle.store.len().unwrap(), 0);
+        assert!(raffle.new_ticket(Some(20), None, None).is_ok());
+        assert!(raffle.new_ticket(None, None, None).is_ok());
+    }
+
+    #[test]
+    fn Raffle_append_ticket_limits() {
+        let mut raffle = Raffle::with_limits(Limits {
+            min_per_request: 1,
+            max_per_request: 20,
+            max_per_ticket: 25,
+        });
+        raffle.new_ticket(None, None, None).unwrap();
+
+        assert!(raffle.append_ticket("1", 0, None).is_err());
+        assert!(raffle.append_ticket("1", 16, None).is_err());
+        assert_eq!(raffle.store.get(1).unwrap().unwrap().state, State::Open);
+        assert_eq!(raffle.append_ticket("1", 15, None).unwrap(), 25);
+        assert!(raffle.append_ticket("1", 1, None).is_err());
+    }
+
+    #[test]
+    fn Raffle_get_ticket_success() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(None, None, None).unwrap();
+        let existing_id = "1";
+
+        assert!(raffle.get_ticket(existing_id).is_ok())
+    }
+
+    #[test]
+    fn Raffle_get_ticket_fail() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(None, None, None).unwrap();
+        let nonexistent_id = "100";
+
+        assert!(raffle.get_ticket(nonexistent_id).is_err())
+    }
+
+    #[test]
+    fn Raffle_append_ticket_success() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(None, None, None).unwrap();
+        let existing_id = "1";
+
+        assert!(raffle.append_ticket(existing_id, 10, None).is_ok())
+    }
+
+    #[test]
+    fn Raffle_append_ticket_fail() {
+        let mut raffle = Raffle::instantiate();
+        let nonexistent_id = "100";
+
+        assert!(raffle.append_ticket(nonexistent_id, 10, None).is_err())
+    }
+
+    #[test]
+    fn Raffle_evaluate_ticket_success() {
+        let mut raffle = Raffle::instantiate();
+        let ticket = static_ticket!();
+        assert!(raffle.store.insert(1, ticket).is_ok());
+
+        assert!(raffle.evaluate_ticket("1").is_ok())
+    }
+
+    #[test]
+    fn Raffle_evaluate_ticket_outcomes() {
+        let mut raffle = Raffle::instantiate();
+        let ticket = static_ticket!();
+        let outcomes = json!(ticket.outcomes(&RuleSet::default()));
+        assert!(raffle.store.insert(1, ticket).is_ok());
+        let result = raffle.evaluate_ticket("1").unwrap();
+
+        let result = json!(result);
+
+        assert_eq!(result["lines"], outcomes);
+        assert_eq!(
+            result["lines"][0]["lines"][0],
+            json!({"index": 15, "numbers": [0, 0, 2], "result": 10})
+        );
+    }
+
+    #[test]
+    fn Raffle_evaluate_ticket_failure() {
+        let mut raffle = Raffle::instantiate();
+        let ticket = static_ticket!();
+        assert!(raffle.store.insert(1, ticket).is_ok());
+
+        assert!(raffle.evaluate_ticket("2").is_err())
+    }
+
+    #[test]
+    fn Raffle_evaluate_ticket_keeps_ticket() {
+        let mut raffle = Raffle::instantiate();
+        let ticket = static_ticket!();
+        assert!(raffle.store.insert(1, ticket).is_ok());
+        let first = raffle.evaluate_ticket("1").unwrap();
+
+        assert_eq!(raffle.store.get(1).unwrap().unwrap().state, State::Checked);
+        assert!(raffle.get_ticket("1").unwrap().checked);
+        assert_eq!(raffle.evaluate_ticket("1").unwrap(), first);
+    }
+
+    #[test]
+    fn Raffle_append_ticket_after_check() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+
+        match raffle.append_ticket("1", 10, None) {
+            Err(ErrorKind::TicketAlreadyChecked(ref id)) if id == "1" => (),
+            other => panic!("expected TicketAlreadyChecked, got {:?}", other),
+        }
+        assert_eq!(raffle.store.get(1).unwrap().unwrap().line_list.len(), 10);
+    }
+
+    #[test]
+    fn Raffle_void_ticket_success() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(None, None, None).unwrap();
+        let voided = raffle.void_ticket("1").unwrap();
+
+        assert_eq!(voided.state, State::Voided);
+        assert_eq!(raffle.get_ticket("1").unwrap(), voided);
+        assert!(raffle.void_ticket("1").is_ok());
+        match raffle.append_ticket("1", 10, None) {
+            Err(ErrorKind::TicketVoided(ref id)) if id == "1" => (),
+            other => panic!("expected TicketVoided, got {:?}", other),
+        }
+        match raffle.evaluate_ticket("1") {
+            Err(ErrorKind::TicketVoided(ref id)) if id == "1" => (),
+            other => panic!("expected TicketVoided, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn Raffle_void_ticket_after_check() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+
+        match raffle.void_ticket("1") {
+            Err(ErrorKind::TicketAlreadyChecked(ref id)) if id == "1" => (),
+            other => panic!("expected TicketAlreadyChecked, got {:?}", other),
+        }
+        assert!(raffle.void_ticket("2").is_err());
+    }
+
+    #[test]
+    fn Raffle_sweep_expires_open() {
+        let mut raffle = Raffle::instantiate();
+        raffle.set_expiry(ExpiryPolicy {
+            open_ttl: Some(60),
+            ..ExpiryPolicy::default()
+        });
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.append_ticket("2", 5, None).unwrap();
+
+        assert_eq!(raffle.sweep_at(now()).unwrap().total(), 0);
+        assert_eq!(
+            raffle.sweep_at(now() + 60).unwrap(),
+            SweepReport {
+                expired: 2,
+                checked: 0,
+                removed: 0,
+            }
+        );
+        assert_eq!(raffle.get_ticket("1").unwrap().state, State::Expired);
+
+        match raffle.append_ticket("1", 5, None) {
+            Err(ErrorKind::TicketExpired(ref id)) if id == "1" => (),
+            other => panic!("expected TicketExpired, got {:?}", other),
+        }
+        match raffle.evaluate_ticket("2") {
+            Err(ErrorKind::TicketExpired(ref id)) if id == "2" => (),
+            other => panic!("expected TicketExpired, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn Raffle_sweep_checks_open() {
+        let mut raffle = Raffle::instantiate();
+        raffle.set_expiry(ExpiryPolicy {
+            open_ttl: Some(60),
+            on_ttl: TtlAction::Check,
+            retention: None,
+        });
+        raffle.new_ticket(None, None, None).unwrap();
+
+        assert_eq!(raffle.sweep_at(now() + 60).unwrap().checked, 1);
+        assert_eq!(raffle.get_ticket("1").unwrap().state, State::Checked);
+        // The result stored by the sweep is what a later check returns
+        let checked = raffle.store.get(1).unwrap().unwrap().result;
+        assert_eq!(raffle.evaluate_ticket("1").ok(), checked);
+    }
+
+    #[test]
+    fn Raffle_sweep_removes_settled() {
+        let mut raffle = Raffle::instantiate();
+        raffle.set_expiry(ExpiryPolicy {
+            retention: Some(30),
+            ..ExpiryPolicy::default()
+        });
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+        raffle.void_ticket("2").unwrap();
+
+        assert_eq!(raffle.sweep_at(now()).unwrap().total(), 0);
+        assert_eq!(raffle.sweep_at(now() + 30).unwrap().removed, 1);
+        assert!(raffle.get_ticket("1").is_err());
+        // Voided Tickets stay for auditing, open ones have no TTL
+        assert_eq!(raffle.get_ticket("2").unwrap().state, State::Voided);
+        assert_eq!(raffle.get_ticket("3").unwrap().state, State::Open);
+    }
+
+    #[test]
+    fn Raffle_sweep_prunes_events() {
+        let mut raffle = Raffle::instantiate();
+        raffle.set_expiry(ExpiryPolicy {
+            retention: Some(30),
+            ..ExpiryPolicy::default()
+        });
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+        assert_eq!(raffle.sweep_at(now() + 30).unwrap().removed, 1);
+
+        // Nothing of the removed Ticket is left in the event stream or the Snapshot
+        let tickets = |events: &[Event]| events.iter().map(|e| e.ticket).collect::<Vec<u64>>();
+        assert_eq!(tickets(&raffle.store.events(None).unwrap()), vec![2]);
+        let snapshot = raffle.snapshot().unwrap();
+        assert_eq!(tickets(&snapshot.events), vec![2]);
+        assert_eq!(snapshot.last_seq, 4);
+
+        // Sequence numbers carry on past the pruned Events, even once restored
+        let mut restored = Raffle::instantiate();
+        restored.restore(snapshot).unwrap();
+        restored.void_ticket("2").unwrap();
+        let seqs: Vec<u64> = restored
+            .store
+            .events(None)
+            .unwrap()
+            .iter()
+            .map(|e| e.seq)
+            .collect();
+        assert_eq!(seqs, vec![2, 5]);
+    }
+
+    #[test]
+    fn Raffle_sweep_default_policy() {
+        let mut raffle = seeded_raffle(3);
+        raffle.evaluate_ticket("1").unwrap();
+
+        assert!(!ExpiryPolicy::default().sweeps());
+        assert_eq!(raffle.sweep_at(u64::max_value()).unwrap().total(), 0);
+    }
+
+    #[test]
+    fn TtlAction_from_str() {
+        assert_eq!("expire".parse::<TtlAction>().unwrap(), TtlAction::Expire);
+        assert_eq!("check".parse::<TtlAction>().unwrap(), TtlAction::Check);
+        match "delete".parse::<TtlAction>() {
+            Err(ErrorKind::InvalidConfig(reason)) => assert!(reason.contains("'delete'")),
+            other => panic!("expected InvalidConfig, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn Raffle_get_ticket_list_voided() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.void_ticket("1").unwrap();
+
+        assert_eq!(raffle.get_ticket_list(false).unwrap().len(), 1);
+        assert_eq!(raffle.get_ticket_list(false).unwrap()[0].id, "2");
+        assert_eq!(raffle.get_ticket_list(true).unwrap().len(), 2);
+    }
+
+    // Fills a Raffle with [n] Tickets of 1..=n Lines, built from the static seed values
+    fn seeded_raffle(n: u64) -> Raffle {
+        let mut raffle = Raffle::instantiate();
+        (1..=n).for_each(|id| {
+            let line_list = LINE_SEED_VALUES
+                .iter()
+                .take(id as usize)
+                .map(|i| Line::from(*i))
+                .collect::<Vec<Line>>();
+            assert_eq!(raffle.store.allocate_id().unwrap(), id);
+            raffle
+                .store
+                .insert(id, Ticket::with_lines(line_list))
+                .unwrap();
+        });
+        raffle
+    }
+
+    #[test]
+    fn Raffle_query_tickets_pages() {
+        let raffle = seeded_raffle(5);
+        let mut query = TicketQuery {
+            limit: 2,
+            ..TicketQuery::default()
+        };
+
+        let first = raffle.query_tickets(&query).unwrap();
+        assert_eq!(first.total, 5);
+        assert_eq!(
+            first
+                .tickets
+                .iter()
+                .map(|t| t.id.parse().unwrap())
+                .collect::<Vec<u64>>(),
+            vec![1, 2]
+        );
+        assert_eq!(first.next, Some(String::from("2")));
+
+        query.after = first.next;
+        let second = raffle.query_tickets(&query).unwrap();
+        assert_eq!(
+            second
+                .tickets
+                .iter()
+                .map(|t| t.id.parse().unwrap())
+                .collect::<Vec<u64>>(),
+            vec![3, 4]
+        );
+
+        query.after = second.next;
+        let last = raffle.query_tickets(&query).unwrap();
+        assert_eq!(
+            last.tickets
+                .iter()
+                .map(|t| t.id.parse().unwrap())
+                .collect::<Vec<u64>>(),
+            vec![5]
+        );
+        assert_eq!(last.next, None);
+    }
+
+    #[test]
+    fn Raffle_query_tickets_filters() {
+        let mut raffle = seeded_raffle(5);
+        raffle.void_ticket("5").unwrap();
+        raffle.evaluate_ticket("4").unwrap();
+
+        let ids = |query: &TicketQuery| {
+            raffle
+                .query_tickets(query)
+                .unwrap()
+                .tickets
+                .iter()
+                .map(|t| t.id.parse().unwrap())
+                .collect::<Vec<u64>>()
+        };
+
+        assert_eq!(ids(&TicketQuery::default()), vec![1, 2, 3, 4]);
+        let voided = TicketQuery {
+            include_voided: true,
+            ..TicketQuery::default()
+        };
+        assert_eq!(ids(&voided), vec![1, 2, 3, 4, 5]);
+        let checked = TicketQuery {
+            state: Some(State::Checked),
+            ..TicketQuery::default()
+        };
+        assert_eq!(ids(&checked), vec![4]);
+        let min_lines = TicketQuery {
+            min_lines: Some(3),
+            ..TicketQuery::default()
+        };
+        assert_eq!(ids(&min_lines), vec![3, 4]);
+        // Totals for 1..=4 lines are 1, 6, 11 and 11
+        let scores = TicketQuery {
+            min_score: Some(6),
+            max_score: Some(10),
+            ..TicketQuery::default()
+        };
+        assert_eq!(ids(&scores), vec![2]);
+    }
+
+    #[test]
+    fn Raffle_query_tickets_ranged() {
+        let mut raffle = seeded_raffle(7);
+        for id in &["2", "3", "4"] {
+            raffle.void_ticket(id).unwrap();
+        }
+        let mut query = TicketQuery {
+            limit: 2,
+            ..TicketQuery::default()
+        };
+        let ids = |page: &TicketPage| {
+            page.tickets
+                .iter()
+                .map(|t| t.id.parse().unwrap())
+                .collect::<Vec<u64>>()
+        };
+
+        // Pages are filled from as many reads as it takes to skip the voided Tickets
+        let first = raffle.query_tickets(&query).unwrap();
+        assert_eq!((ids(&first), first.total), (vec![1, 5], 4));
+        query.after = first.next;
+        let last = raffle.query_tickets(&query).unwrap();
+        assert_eq!((ids(&last), last.total), (vec![6, 7], 4));
+        assert_eq!(last.next, None);
+
+        let voided = TicketQuery {
+            state: Some(State::Voided),
+            ..TicketQuery::default()
+        };
+        let page = raffle.query_tickets(&voided).unwrap();
+        assert_eq!((ids(&page), page.total), (vec![2, 3, 4], 3));
+    }
+
+    #[test]
+    fn Raffle_query_tickets_sorting() {
+        let raffle = seeded_raffle(4);
+        let by_score = TicketQuery {
+            sort: SortKey::Score,
+            descending: true,
+            limit: 2,
+            ..TicketQuery::default()
+        };
+
+        let first = raffle.query_tickets(&by_score).unwrap();
+        assert_eq!(
+            first
+                .tickets
+                .iter()
+                .map(|t| t.id.parse().unwrap())
+                .collect::<Vec<u64>>(),
+            vec![4, 3]
+        );
+        assert_eq!(first.next, Some(String::from("11.3")));
+
+        let second = raffle
+            .query_tickets(&TicketQuery {
+                after: first.next,
+                ..by_score.clone()
+            })
+            .unwrap();
+        assert_eq!(
+            second
+                .tickets
+                .iter()
+                .map(|t| t.id.parse().unwrap())
+                .collect::<Vec<u64>>(),
+            vec![2, 1]
+        );
+        assert_eq!(second.next, None);
+
+        let malformed = TicketQuery {
+            after: Some(String::from("eleven")),
+            ..TicketQuery::default()
+        };
+        assert!(raffle.query_tickets(&malformed).is_err());
+    }
+
+    #[test]
+    fn Raffle_snapshot_restore() {
+        let mut raffle = seeded_raffle(3);
+        raffle.evaluate_ticket("2").unwrap();
+        raffle.void_ticket("3").unwrap();
+        let snapshot = raffle.snapshot().unwrap();
+        assert_eq!(snapshot.version, SNAPSHOT_VERSION);
+        assert_eq!(snapshot.last_id, 3);
+
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let mut restored = Raffle::instantiate();
+        restored
+            .restore(serde_json::from_str(&json).unwrap())
+            .unwrap();
+
+        assert_eq!(restored.snapshot().unwrap(), snapshot);
+        assert_eq!(
+            restored.get_ticket("2").unwrap(),
+            raffle.get_ticket("2").unwrap()
+        );
+        assert_eq!(
+            restored.evaluate_ticket("2").unwrap(),
+            raffle.evaluate_ticket("2").unwrap()
+        );
+        assert_eq!(restored.new_ticket(None, None, None).unwrap(), "4");
+    }
+
+    // Journal keeping its Events where a test can see them, or refusing every write
+    struct TestJournal(std::sync::Arc<std::sync::Mutex<Vec<Event>>>, bool);
+
+    impl Journal for TestJournal {
+        fn record(&mut self, event: &Event) -> Result<(), ErrorKind> {
+            match self.1 {
+                true => Err(ErrorKind::Storage(String::from("read only"))),
+                false => Ok(self.0.lock().unwrap().push(event.clone())),
+            }
+        }
+
+        fn record_ruleset(&mut self, _: &RulesetVersion) -> Result<(), ErrorKind> {
+            Ok(())
+        }
+
+        fn rollback(&mut self) -> Result<(), ErrorKind> {
+            self.0.lock().unwrap().pop();
+            Ok(())
+        }
+
+        fn truncate(&mut self) -> Result<(), ErrorKind> {
+            Ok(self.0.lock().unwrap().clear())
+        }
+    }
+
+    #[test]
+    fn Raffle_journal_replay() {
+        let entries = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut raffle = Raffle::instantiate();
+        raffle
+            .set_journal(Box::new(TestJournal(entries.clone(), false)))
+            .unwrap();
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.append_ticket("1", 3, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+        raffle.void_ticket("2").unwrap();
+
+        let seqs: Vec<(u64, u64)> = entries
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| (e.seq, e.ticket))
+            .collect();
+        assert_eq!(seqs, vec![(1, 1), (2, 2), (3, 1), (4, 1), (5, 2)]);
+
+        // Replaying an Event twice changes nothing
+        let mut replayed = Raffle::instantiate();
+        let events = entries.lock().unwrap().clone();
+        for event in events.iter().chain(events.iter()) {
+            replayed.replay(event.clone()).unwrap();
+        }
+        assert_eq!(replayed.snapshot().unwrap(), raffle.snapshot().unwrap());
+
+        raffle.truncate_journal().unwrap();
+        assert!(entries.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn Raffle_get_history() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(Some(2), None, Some("alice")).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.append_ticket("1", 1, Some("bob")).unwrap();
+        let result = raffle.evaluate_ticket("1").unwrap();
+
+        let history = raffle.get_history("1").unwrap();
+        assert_eq!(history.id, "1");
+        let seqs: Vec<u64> = history.events.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![1, 3, 4]);
+        let ticket = raffle.store.get(1).unwrap().unwrap();
+        assert_eq!(
+            history.events[0].kind,
+            EventKind::TicketCreated {
+                public_id: None,
+                game: String::from(CLASSIC_GAME),
+                rules: Some(RulesetPin {
+                    id: String::from(CLASSIC_GAME),
+                    version: 1,
+                }),
+                lines: ticket.line_list[..2].to_vec(),
+                requester: Some(String::from("alice")),
+            }
+        );
+        assert_eq!(
+            history.events[1].kind,
+            EventKind::LinesAppended {
+                lines: ticket.line_list[2..].to_vec(),
+                requester: Some(String::from("bob")),
+            }
+        );
+        assert_eq!(
+            history.events[2].kind,
+            EventKind::TicketChecked {
+                score: result.score,
+                message: result.message,
+            }
+        );
 
-        Ticket { line_list }
+        let json = serde_json::to_value(&history).unwrap();
+        assert_eq!(json["events"][0]["event"], json!("ticket_created"));
+        assert_eq!(json["events"][2]["event"], json!("ticket_checked"));
+        assert!(raffle.get_history("3").is_err());
     }
 
-    // Appends N [additional] lines to an existing ticket
-    fn append(&mut self, additional: u64) {
-        (0..additional)
-            .scan((rng(), Uniform::from(0..3)), |(s, r), _| {
-                Some((r.sample(s), r.sample(s), r.sample(s)))
-            })
-            .for_each(|seed| self.line_list.push(Line::from(seed)))
+    #[test]
+    fn Event_serde() {
+        let event = Event {
+            seq: 7,
+            ticket: 2,
+            at: 1_500_000_000,
+            kind: EventKind::LinesAppended {
+                lines: vec![Line::from((2, 2, 0))],
+                requester: None,
+            },
+        };
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["event"], json!("lines_appended"));
+        assert_eq!(json["seq"], json!(7));
+        assert_eq!(serde_json::from_value::<Event>(json).unwrap(), event);
     }
 
-    // Computes the output for all Lines in a Ticket
-    fn eval_list(&self) -> Vec<u8> {
-        self.line_list
-            .iter()
-            .map(|line| line.eval_line())
-            .collect::<Vec<u8>>()
+    #[test]
+    fn Raffle_rebuild() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.append_ticket("1", 2, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+        raffle.void_ticket("2").unwrap();
+        raffle.set_expiry(ExpiryPolicy {
+            open_ttl: Some(0),
+            on_ttl: TtlAction::Expire,
+            retention: None,
+        });
+        assert_eq!(raffle.sweep_at(now()).unwrap().expired, 1);
+        let snapshot = raffle.snapshot().unwrap();
+
+        raffle.store.remove(3).unwrap();
+        raffle
+            .store
+            .update(1, Ticket::with_lines(vec![Line::from((0, 0, 0))]))
+            .unwrap();
+        assert_eq!(raffle.rebuild().unwrap(), 3);
+        assert_eq!(raffle.snapshot().unwrap(), snapshot);
     }
-}
 
-impl fmt::Display for Ticket {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "[")?;
-        for (i, line) in self.line_list.iter().enumerate() {
-            if i != 0 {
-                write!(f, " ")?;
+    #[test]
+    fn Raffle_project() {
+        // Counts how many lines were ever handed out
+        struct LineCount(usize);
+
+        impl Projection for LineCount {
+            fn apply(&mut self, event: &Event) -> Result<(), ErrorKind> {
+                match &event.kind {
+                    EventKind::TicketCreated { lines, .. } => self.0 += lines.len(),
+                    EventKind::LinesAppended { lines, .. } => self.0 += lines.len(),
+                    _ => (),
+                }
+                Ok(())
             }
-            write!(f, "{}", line)?;
         }
-        write!(f, "]")
-    }
-}
 
-// Named tuple which holds 3 numbers between 0 and 2: [0,1,2]
-#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
-struct Line(u8, u8, u8);
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.new_ticket(Some(3), None, None).unwrap();
+        raffle.append_ticket("1", 4, None).unwrap();
+        raffle.void_ticket("2").unwrap();
+
+        let mut count = LineCount(0);
+        assert_eq!(raffle.project(&mut count).unwrap(), 4);
+        assert_eq!(count.0, 9);
+    }
 
-impl Line {
-    // A Line can only be generated by a Ticket
-    fn from((x, y, z): (u8, u8, u8)) -> Self {
-        Line(x, y, z)
+    #[test]
+    fn Raffle_adopt_untracked() {
+        let mut raffle = seeded_raffle(2);
+        raffle.new_ticket(None, None, None).unwrap();
+        assert_eq!(raffle.adopt_untracked().unwrap(), 2);
+        assert_eq!(raffle.adopt_untracked().unwrap(), 0);
+
+        let history = raffle.get_history("2").unwrap();
+        assert_eq!(history.events.len(), 1);
+        let snapshot = raffle.snapshot().unwrap();
+        raffle.rebuild().unwrap();
+        assert_eq!(raffle.snapshot().unwrap(), snapshot);
     }
 
-    // Computes a Line's output based on the given rules
-    fn eval_line(&self) -> u8 {
-        match (self.0, self.1, self.2) {
-            // Ordered by rule priority
-            // Avoids situations where ex. (2,0,0) satisfies
-            // 2 rules: |x+y+z == 2| & |x!=y && x!=z|
-            (x, y, z) if x + y + z == 2 => 10,
-            (x, y, z) if x == y && y == z => 5,
-            (x, y, z) if x != y && x != z => 1,
-            (_, _, _) => 0,
+    #[test]
+    fn Raffle_export_import() {
+        let mut raffle = seeded_raffle(3);
+        raffle.evaluate_ticket("1").unwrap();
+        raffle.void_ticket("2").unwrap();
+        let export = raffle.export().unwrap();
+        assert_eq!(export.version, EXPORT_VERSION);
+        assert_eq!(export.ticket_count, 3);
+        assert_eq!(export.last_id, 3);
+
+        let json = serde_json::to_value(&export).unwrap();
+        assert_eq!(json["tickets"][1]["state"], json!("voided"));
+        let mut imported = Raffle::instantiate();
+        let report = imported
+            .import(serde_json::from_value(json).unwrap(), ImportMode::Merge)
+            .unwrap();
+        assert_eq!(report.imported, 3);
+        assert!(report.collisions.is_empty());
+        assert_eq!(
+            imported.snapshot().unwrap().tickets,
+            raffle.snapshot().unwrap().tickets
+        );
+        // The imported Tickets' histories start with the import
+        let history = imported.get_history("1").unwrap();
+        assert_eq!(history.events.len(), 1);
+        match &history.events[0].kind {
+            EventKind::TicketImported { ticket } => assert_eq!(ticket.state, State::Checked),
+            kind => panic!("unexpected event {:?}", kind),
         }
+        assert_eq!(imported.new_ticket(None, None, None).unwrap(), "4");
     }
-}
 
-impl fmt::Display for Line {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "|{}|", self.eval_line())
+    #[test]
+    fn Raffle_import_merge_collisions() {
+        let mut raffle = seeded_raffle(2);
+        let export = seeded_raffle(3).export().unwrap();
+
+        let report = raffle.import(export, ImportMode::Merge).unwrap();
+        assert_eq!(report.imported, 1);
+        assert_eq!(report.collisions, vec![1, 2]);
+        assert_eq!(raffle.store.len().unwrap(), 3);
     }
-}
-
-// Error kind(s) used by Raffle
-#[derive(Debug, Clone)]
-pub enum ErrorKind {
-    TicketNotFound(u64),
-}
 
-impl fmt::Display for ErrorKind {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self {
-            ErrorKind::TicketNotFound(id) => write!(f, "Ticket id: {} doesn't exist", id),
-        }
+    #[test]
+    fn Raffle_import_replace() {
+        let mut raffle = seeded_raffle(5);
+        let export = seeded_raffle(2).export().unwrap();
+
+        let report = raffle.import(export, ImportMode::Replace).unwrap();
+        assert_eq!(report.imported, 2);
+        assert_eq!(raffle.store.len().unwrap(), 2);
+        // IDs 3 to 5 were handed out before, so they aren't reused
+        assert_eq!(report.last_id, 5);
+        assert_eq!(raffle.new_ticket(None, None, None).unwrap(), "6");
     }
-}
-
-const TERRIBLE: [&str; 3] = [
-    "you get nothing; good day sir!",
-    "I award you no points, and may...",
-    "I bet you have terrible anxiety!",
-];
-const MEDIOCRE: [&str; 3] = [
-    "slightly better than a hostel shower!",
-    "confidence is your prize!",
-    "its lonely being stuck in the middle!",
-];
-const AVERAGE: [&str; 3] = [
-    "you're one of today's lucky 10,000!",
-    "mostly better than half even...",
-    "delta Vee / delta Tee",
-];
-const GREAT: [&str; 3] = [
-    "almost enough for a cheese pizza!",
-    "thinking like a queen!",
-    "heralding from simplicity, goodness and truth",
-];
-const JACKPOT: [&str; 3] = [
-    "ding ding ding, you won the imaginary jackpot!",
-    "winner winner, chicken dinner!",
-    "supercalifragilisticexpialidocious",
-];
-
-/*
-Code
--------------------------------------------------------------------------------
-Tests
-*/
 
-#[cfg(test)]
-mod tests {
-    #![allow(non_snake_case)]
-    use super::*;
+    #[test]
+    fn Raffle_import_validation() {
+        let mut json = serde_json::to_value(seeded_raffle(2).export().unwrap()).unwrap();
+        json["tickets"][0]["line_list"][0] = json!([0, 7, 1]);
+        json["tickets"][1]["id"] = json!(1);
+        json["tickets"][1]["batches"][0]["end"] = json!(5);
+        json["ticket_count"] = json!(5);
+        let mut raffle = Raffle::instantiate();
 
-    macro_rules! static_ticket {
-        () => {{
-            let line_list = LINE_SEED_VALUES
-                .iter()
-                .map(|i| Line::from(*i))
-                .collect::<Vec<Line>>();
-            let ticket = Ticket { line_list };
-            ticket
-        }};
+        match raffle.import(serde_json::from_value(json).unwrap(), ImportMode::Replace) {
+            Err(ErrorKind::InvalidImport(problems)) => {
+                assert_eq!(problems.len(), 4);
+                assert!(problems[1].contains("0..=2"));
+                assert!(problems[3].contains("batches"));
+            }
+            other => panic!("expected InvalidImport, got {:?}", other),
+        }
+        assert_eq!(raffle.store.len().unwrap(), 0);
     }
 
-    // ErrorKind tests
     #[test]
-    fn TicketNotFound_display() {
-        assert_eq!(
-            format!("{}", ErrorKind::TicketNotFound(42)),
-            "Ticket id: 42 doesn't exist"
-        )
+    fn Raffle_restore_misshapen() {
+        let raffle = seeded_raffle(2);
+        let mut snapshot = raffle.snapshot().unwrap();
+        snapshot.tickets[1].1.line_list[0] = Line(Vec::new());
+
+        // Nothing is loaded from a snapshot holding a Line its Game couldn't draw
+        let mut restored = Raffle::instantiate();
+        match restored.restore(snapshot) {
+            Err(ErrorKind::Storage(reason)) => {
+                assert!(reason.starts_with("unusable snapshot: ticket 2 line 0"))
+            }
+            other => panic!("expected Storage, got {:?}", other),
+        }
+        assert_eq!(restored.store.len().unwrap(), 0);
+
+        // ...nor replayed from a journal
+        let appended = Event {
+            seq: 1,
+            ticket: 1,
+            at: now(),
+            kind: EventKind::LinesAppended {
+                lines: vec![Line(vec![0, 1, 2, 0])],
+                requester: None,
+            },
+        };
+        let mut replayed = seeded_raffle(1);
+        match replayed.replay(appended) {
+            Err(ErrorKind::Storage(reason)) => {
+                assert!(reason.starts_with("unusable journal event 1: ticket 1 line 1"))
+            }
+            other => panic!("expected Storage, got {:?}", other),
+        }
+        assert_eq!(replayed.get_ticket("1").unwrap().line_count, 1);
     }
 
-    // Line tests
+    #[test]
+    fn Raffle_journal_failure() {
+        let entries = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let mut raffle = seeded_raffle(1);
+        raffle
+            .set_journal(Box::new(TestJournal(entries, true)))
+            .unwrap();
+
+        match raffle.append_ticket("1", 1, None) {
+            Err(ErrorKind::Storage(_)) => (),
+            other => panic!("expected Storage, got {:?}", other),
+        }
+        assert_eq!(raffle.get_ticket("1").unwrap().state, State::Open);
+        assert!(raffle.new_ticket(None, None, None).is_err());
+        assert_eq!(raffle.store.len().unwrap(), 1);
+    }
 
     #[test]
-    fn Line_from() {
-        LINE_SEED_VALUES
-            .iter()
-            .for_each(|seed| assert_eq!(Line::from(*seed), Line(seed.0, seed.1, seed.2)));
+    fn Raffle_journal_rollback() {
+        let path = std::env::temp_dir().join(format!("raffle-rollback-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let entries = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let store = SqliteStore::open(&path).unwrap();
+        let mut raffle = Raffle::with_store(Box::new(store), Limits::default());
+        raffle
+            .set_journal(Box::new(TestJournal(entries.clone(), false)))
+            .unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+
+        // An Event the store refuses to commit is taken back out of the journal
+        rusqlite::Connection::open(&path)
+            .unwrap()
+            .execute_batch(
+                "CREATE TRIGGER refuse BEFORE INSERT ON events
+                 BEGIN SELECT RAISE(ABORT, 'refused'); END;",
+            )
+            .unwrap();
+        match raffle.append_ticket("1", 1, None) {
+            Err(ErrorKind::Storage(_)) => (),
+            other => panic!("expected Storage, got {:?}", other),
+        }
+        assert_eq!(entries.lock().unwrap().len(), 1);
+        std::fs::remove_file(&path).unwrap();
     }
 
     #[test]
-    fn Line_eval_line() {
-        LINE_SEED_VALUES
-            .iter()
-            .zip(LINE_EVAL_VALUES.iter())
-            .map(|(seed, value)| (Line::from(*seed).eval_line(), *value))
-            .for_each(|(eval, value)| assert_eq!(eval, value))
+    fn Raffle_opaque_ids() {
+        let mut raffle = seeded_raffle(1);
+        raffle.set_id_scheme(IdScheme::Ulid);
+        let id = raffle.new_ticket(None, None, None).unwrap();
+        assert_eq!(id.len(), 26);
+
+        assert_eq!(raffle.get_ticket(&id).unwrap().id, id);
+        assert_eq!(raffle.evaluate_ticket(&id).unwrap().id, id);
+        // The new Ticket's key can't be used to find it...
+        match raffle.get_ticket("2") {
+            Err(ErrorKind::TicketNotFound(ref missing)) if missing == "2" => (),
+            other => panic!("expected TicketNotFound, got {:?}", other),
+        }
+        // ...but Tickets from before the switch are still known by theirs
+        assert_eq!(raffle.get_ticket("1").unwrap().id, "1");
+        assert!(raffle.get_ticket("01").is_err());
     }
 
     #[test]
-    fn Line_display() {
-        let line = Line::from(LINE_SEED_VALUES[0]);
-        let evaluated = LINE_EVAL_VALUES[0];
+    fn Raffle_query_tickets_opaque_cursor() {
+        let mut raffle = seeded_raffle(1);
+        raffle.set_id_scheme(IdScheme::Ulid);
+        let first = raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        let by_id = TicketQuery {
+            limit: 2,
+            ..TicketQuery::default()
+        };
+        let by_lines = TicketQuery {
+            sort: SortKey::Lines,
+            ..by_id.clone()
+        };
 
-        assert_eq!(format!("{}", line), format!("|{}|", evaluated));
+        // Cursors name the last Ticket by its ID, never by its key
+        let page = raffle.query_tickets(&by_id).unwrap();
+        assert_eq!(page.next, Some(first.clone()));
+        let page = raffle.query_tickets(&by_lines).unwrap();
+        assert_eq!(page.next, Some(format!("2.{}", first)));
+        let last = raffle
+            .query_tickets(&TicketQuery {
+                after: page.next,
+                ..by_lines.clone()
+            })
+            .unwrap();
+        assert_eq!(last.tickets.len(), 1);
+        assert_eq!(last.next, None);
+
+        // A cursor whose Ticket is gone can't be resumed from
+        raffle.store.remove(2).unwrap();
+        match raffle.query_tickets(&TicketQuery {
+            after: Some(first),
+            ..by_id
+        }) {
+            Err(ErrorKind::InvalidQuery(_)) => (),
+            other => panic!("expected InvalidQuery, got {:?}", other),
+        }
     }
 
-    // Ticket tests
     #[test]
-    fn Ticket_new() {
-        let ticket = Ticket::new();
-        let ticket2 = Ticket::new();
-        // It is theoretically possible for this test to fail due the inherent randomness;
-        // therefore check against a third rand generation, before failing the test
-        assert!(ticket != ticket2 || ticket != Ticket::new())
+    fn IdScheme_generate() {
+        assert_eq!(IdScheme::Sequential.generate(), None);
+        assert_eq!(IdScheme::Ulid.generate().unwrap().len(), 26);
+        assert_eq!(IdScheme::Uuid.generate().unwrap().len(), 36);
+
+        let base32 = IdScheme::Base32.generate().unwrap();
+        assert_eq!(base32.len(), 26);
+        assert_eq!(base32, base32.to_lowercase());
+        assert_ne!(IdScheme::Base32.generate().unwrap(), base32);
     }
 
     #[test]
-    fn Ticket_from() {
-        (1..=10).for_each(|lines_num| {
-            assert_eq!(Ticket::from(lines_num).line_list.len(), lines_num as usize)
-        })
+    fn IdScheme_from_str() {
+        assert_eq!("ulid".parse::<IdScheme>().unwrap(), IdScheme::Ulid);
+        assert_eq!("base32".parse::<IdScheme>().unwrap(), IdScheme::Base32);
+        match "guid".parse::<IdScheme>() {
+            Err(ErrorKind::InvalidConfig(reason)) => assert!(reason.contains("'guid'")),
+            other => panic!("expected InvalidConfig, got {:?}", other),
+        }
     }
 
     #[test]
-    fn Ticket_append() {
-        let start_size = 10;
-        let additional_lines = 7;
-        let end_size = 17;
-        assert_eq!(start_size + additional_lines, end_size);
+    fn StatusResult_numeric_id() {
+        let result: StatusResult =
+            serde_json::from_value(json!({"id": 7, "score": 1, "message": "", "lines": []}))
+                .unwrap();
+        assert_eq!(result.id, "7");
+    }
 
-        let mut ticket = Ticket::from(start_size);
-        ticket.append(additional_lines);
-        assert_eq!(ticket.line_list.len(), end_size as usize);
+    #[test]
+    fn Raffle_get_ticket_list_type() {
+        let raffle = Raffle::instantiate();
+        assert!(json!(raffle.get_ticket_list(false).unwrap()).is_array())
     }
 
     #[test]
-    fn Ticket_eval_list() {
-        let line_list: Vec<Line> = LINE_SEED_VALUES.iter().map(|i| Line::from(*i)).collect();
-        let ticket = Ticket { line_list };
+    fn Raffle_get_ticket_list_composition() {
+        let mut raffle = Raffle::instantiate();
+        let ticket = static_ticket!();
+        let (idx, at) = (1, ticket.created_at());
+        assert!(raffle.store.insert(idx, ticket).is_ok());
+        let output: Vec<jVal> = LINE_SEED_VALUES
+            .iter()
+            .zip(LINE_EVAL_VALUES.iter())
+            .enumerate()
+            .map(|(index, ((x, y, z), result))| {
+                json!({"index": index, "numbers": [x, y, z], "result": result})
+            })
+            .collect();
+
         assert_eq!(
-            ticket.eval_list(),
-            LINE_EVAL_VALUES.iter().map(|i| *i).collect::<Vec<u8>>()
+            json!(raffle.get_ticket_list(false).unwrap()),
+            json!([{
+                "id": idx.to_string(),
+                "game": CLASSIC_GAME,
+                "rules": {"id": CLASSIC_GAME, "version": 1},
+                "state": "open",
+                "checked": false,
+                "line_count": LINE_SEED_VALUES.len(),
+                "total_score": LINE_EVAL_VALUES.iter().map(|i| *i as u64).sum::<u64>(),
+                "created_at": at,
+                "updated_at": at,
+                "checked_at": null,
+                "batches": [{
+                    "at": at,
+                    "start": 0,
+                    "end": LINE_SEED_VALUES.len(),
+                    "requester": null,
+                }],
+                "lines": output,
+            }])
         );
     }
+
     #[test]
-    fn Ticket_display() {
-        let line_list: Vec<Line> = LINE_SEED_VALUES
+    fn Raffle_amendment_history() {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(Some(10), None, Some("alice")).unwrap();
+        raffle.append_ticket("1", 5, Some("bob")).unwrap();
+        raffle.append_ticket("1", 2, None).unwrap();
+        let ticket = raffle.get_ticket("1").unwrap();
+        assert_eq!(ticket.checked_at, None);
+
+        let ranges: Vec<(u64, u64, Option<&str>)> = ticket
+            .batches
             .iter()
-            .take(10)
-            .map(|i| Line::from(*i))
+            .map(|b| (b.start, b.end, b.requester.as_ref().map(|r| r.as_str())))
             .collect();
-        let ticket = Ticket { line_list };
-        let evals: Vec<u8> = LINE_EVAL_VALUES.iter().take(10).map(|i| *i).collect();
-        assert_eq!(ticket.line_list.len(), evals.len());
-
         assert_eq!(
-            format!("{}", ticket),
-            format!(
-                "[|{}| |{}| |{}| |{}| |{}| |{}| |{}| |{}| |{}| |{}|]",
-                evals[0],
-                evals[1],
-                evals[2],
-                evals[3],
-                evals[4],
-                evals[5],
-                evals[6],
-                evals[7],
-                evals[8],
-                evals[9],
-            )
-        )
+            ranges,
+            vec![
+                (0, 10, Some("alice")),
+                (10, 15, Some("bob")),
+                (15, 17, None)
+            ]
+        );
+        assert!(ticket.batches.iter().all(|b| b.at >= ticket.created_at));
+
+        raffle.evaluate_ticket("1").unwrap();
+        let ticket = raffle.get_ticket("1").unwrap();
+        assert_eq!(ticket.checked_at, Some(ticket.updated_at));
+        assert!(ticket.created_at <= ticket.updated_at);
     }
 
-    // Raffle tests
     #[test]
-    fn Raffle_instantiate() {
-        let base_raffle = Raffle::instantiate();
-
-        assert!(base_raffle.count == 0 && base_raffle.tickets.is_empty());
+    fn Ticket_without_batches() {
+        // Tickets stored before amendments were recorded still load, without any
+        let mut stored = json!(Ticket::from(3));
+        stored.as_object_mut().unwrap().remove("batches");
+        let ticket: Ticket = serde_json::from_value(stored).unwrap();
+        assert!(ticket.batches.is_empty());
     }
 
     #[test]
-    fn Raffle_new_ticket_default() {
+    fn Raffle_get_transitions() {
         let mut raffle = Raffle::instantiate();
-        let ticket_id = raffle.new_ticket(None);
-
-        assert!(raffle.count == 1 && raffle.tickets.len() == 1 && ticket_id == 1)
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.append_ticket("1", 5, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+        let transitions = json!(raffle.get_transitions("1").unwrap());
+
+        assert_eq!(transitions["state"], json!("checked"));
+        assert_eq!(transitions["transitions"][0]["to"], json!("open"));
+        assert_eq!(transitions["transitions"][1]["to"], json!("amended"));
+        assert_eq!(transitions["transitions"][2]["from"], json!("amended"));
+        assert_eq!(transitions["transitions"][2]["to"], json!("checked"));
+        assert!(raffle.get_transitions("2").is_err());
     }
 
     #[test]
-    fn Raffle_new_ticket_with_lines() {
+    fn Raffle_get_ticket_numbers() {
         let mut raffle = Raffle::instantiate();
-        let len80 = 80;
-        let ticket_id = raffle.new_ticket(Some(len80));
+        assert!(raffle.store.insert(1, static_ticket!()).is_ok());
+        let ticket = json!(raffle.get_ticket("1").unwrap());
 
-        assert!(
-            raffle.count == 1
-                && raffle.tickets.len() == 1
-                && ticket_id == *raffle.tickets.keys().nth(0).unwrap()
-                && len80 as usize == raffle.tickets.get(&ticket_id).unwrap().line_list.len()
+        assert_eq!(
+            ticket["lines"][1],
+            json!({"index": 1, "numbers": [2, 2, 2], "result": 5})
+        );
+    }
+
+    // Serves the classic game alongside a wide game, which is the default
+    fn two_games() -> Games {
+        Games::from_toml(
+            r#"
+            default = "wide"
+
+            [[game]]
+            name = "classic"
+
+            [[game]]
+            name = "wide"
+            line_width = 5
+            line_symbols = 5
+            default_lines = 4
+            "#,
         )
+        .unwrap()
     }
 
     #[test]
-    fn Raffle_get_ticket_success() {
+    fn Raffle_games() {
         let mut raffle = Raffle::instantiate();
-        raffle.new_ticket(None);
-        let existing_id = 1;
-
-        assert!(raffle.get_ticket(existing_id).is_ok())
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.set_games(two_games()).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.new_ticket(Some(1), Some("classic"), None).unwrap();
+        match raffle.new_ticket(None, Some("missing"), None) {
+            Err(ErrorKind::UnknownGame(name)) => assert_eq!(name, "missing"),
+            other => panic!("expected UnknownGame, got {:?}", other),
+        }
+        raffle.append_ticket("1", 1, None).unwrap();
+        raffle.append_ticket("2", 1, None).unwrap();
+
+        // Every Ticket's Lines are drawn in the shape of its own Game, Tickets from
+        // before there were games belong to the classic game
+        let widths = |id| {
+            raffle
+                .store
+                .get(id)
+                .unwrap()
+                .unwrap()
+                .line_list
+                .iter()
+                .map(|line| line.numbers().len())
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(widths(1), vec![3, 3, 3]);
+        assert_eq!(widths(2), vec![5, 5, 5, 5, 5]);
+        assert_eq!(widths(3), vec![3]);
+        let games: Vec<String> = (1..=3)
+            .map(|id| raffle.get_ticket(&id.to_string()).unwrap().game)
+            .collect();
+        assert_eq!(games, vec!["classic", "wide", "classic"]);
+        assert!(raffle.evaluate_ticket("2").is_ok());
+
+        let views = raffle.get_games();
+        assert_eq!(views.len(), 2);
+        assert_eq!(raffle.game(None).unwrap().name, "wide");
+
+        // Tickets in a Game no longer served can't be amended or checked
+        raffle.new_ticket(Some(1), None, None).unwrap();
+        raffle.set_games(Games::default()).unwrap();
+        match raffle.append_ticket("4", 1, None) {
+            Err(ErrorKind::UnknownGame(name)) => assert_eq!(name, "wide"),
+            other => panic!("expected UnknownGame, got {:?}", other),
+        }
+        match raffle.evaluate_ticket("4") {
+            Err(ErrorKind::UnknownGame(name)) => assert_eq!(name, "wide"),
+            other => panic!("expected UnknownGame, got {:?}", other),
+        }
+        assert!(raffle.evaluate_ticket("2").is_ok());
+        assert!(raffle.evaluate_ticket("3").is_ok());
     }
 
     #[test]
-    fn Raffle_get_ticket_fail() {
+    fn Raffle_line_shape() {
         let mut raffle = Raffle::instantiate();
-        raffle.new_ticket(None);
-        let nonexistent_id = 100;
-
-        assert!(raffle.get_ticket(nonexistent_id).is_err())
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        let mut wide = Game::classic();
+        wide.shape = LineShape::new(5, 5).unwrap();
+        raffle.set_games(Games::single(wide)).unwrap();
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.append_ticket("1", 1, None).unwrap();
+
+        // Existing Lines keep their width, new ones are drawn in the new shape
+        let widths = |id| {
+            raffle
+                .store
+                .get(id)
+                .unwrap()
+                .unwrap()
+                .line_list
+                .iter()
+                .map(|line| line.numbers().len())
+                .collect::<Vec<usize>>()
+        };
+        assert_eq!(widths(1), vec![3, 3, 5]);
+        assert_eq!(widths(2), vec![5, 5]);
+        // and every width is scored by the same rules
+        assert!(raffle.evaluate_ticket("2").is_ok());
+
+        // Imports are checked against the current shape
+        let export = seeded_raffle(1).export().unwrap();
+        match raffle.import(export, ImportMode::Replace) {
+            Err(ErrorKind::InvalidImport(problems)) => {
+                assert!(problems[0].ends_with("has 3 numbers, lines must have 5"))
+            }
+            other => panic!("expected InvalidImport, got {:?}", other),
+        }
     }
 
     #[test]
-    fn Raffle_append_ticket_success() {
+    fn Raffle_games_rebuild() {
         let mut raffle = Raffle::instantiate();
-        raffle.new_ticket(None);
-        let existing_id = 1;
-
-        assert!(raffle.append_ticket(existing_id, 10).is_ok())
+        raffle.set_games(two_games()).unwrap();
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        let before = raffle.get_ticket("1").unwrap();
+
+        // The Game a Ticket was created in survives deriving it again from its Events
+        raffle.rebuild().unwrap();
+        assert_eq!(raffle.get_ticket("1").unwrap(), before);
+        assert_eq!(before.game, "wide");
     }
 
     #[test]
-    fn Raffle_append_ticket_fail() {
+    fn Raffle_import_games() {
         let mut raffle = Raffle::instantiate();
-        let nonexistent_id = 100;
+        raffle
+            .set_games(Games::from_toml("[[game]]\nname = \"wide\"\nline_width = 5\n").unwrap())
+            .unwrap();
+
+        // Classic Tickets are checked against the default Game when there is no classic game
+        let export = seeded_raffle(1).export().unwrap();
+        match raffle.import(export, ImportMode::Replace) {
+            Err(ErrorKind::InvalidImport(problems)) => {
+                assert!(problems[0].ends_with("has 3 numbers, lines must have 5"))
+            }
+            other => panic!("expected InvalidImport, got {:?}", other),
+        }
 
-        assert!(raffle.append_ticket(nonexistent_id, 10).is_err())
+        let mut json = serde_json::to_value(seeded_raffle(1).export().unwrap()).unwrap();
+        json["tickets"][0]["game"] = json!("missing");
+        match raffle.import(serde_json::from_value(json).unwrap(), ImportMode::Replace) {
+            Err(ErrorKind::InvalidImport(problems)) => {
+                assert_eq!(problems, vec!["ticket 1 belongs to unknown game 'missing'"])
+            }
+            other => panic!("expected InvalidImport, got {:?}", other),
+        }
     }
 
-    #[test]
-    fn Raffle_evaluate_ticket_success() {
-        let mut raffle = Raffle::instantiate();
-        let ticket = static_ticket!();
-        assert!(raffle.tickets.insert(1, ticket).is_none());
+    // Serves the classic game with rules that never pay out
+    fn unpaid_games() -> Games {
+        let mut classic = Game::classic();
+        classic.rules = RuleSet::new(Vec::new());
+        Games::single(classic)
+    }
 
-        assert!(raffle.evaluate_ticket(1).is_ok())
+    fn classic_pin(version: u32) -> Option<RulesetPin> {
+        Some(RulesetPin {
+            id: String::from(CLASSIC_GAME),
+            version,
+        })
     }
 
     #[test]
-    fn Raffle_evaluate_ticket_failure() {
+    fn Raffle_rulesets_pinned() {
         let mut raffle = Raffle::instantiate();
-        let ticket = static_ticket!();
-        assert!(raffle.tickets.insert(1, ticket).is_none());
+        raffle.set_games(Games::default()).unwrap();
+        raffle.new_ticket(Some(3), None, None).unwrap();
+        let before = raffle.get_ticket("1").unwrap();
+        assert_eq!(before.rules, classic_pin(1));
+
+        // Changed rules only apply to Tickets created after the change
+        raffle.set_games(unpaid_games()).unwrap();
+        raffle.new_ticket(Some(3), None, None).unwrap();
+        assert_eq!(raffle.get_ticket("1").unwrap(), before);
+        let after = raffle.get_ticket("2").unwrap();
+        assert_eq!(after.rules, classic_pin(2));
+        assert_eq!(after.total_score, 0);
+
+        let ticket = raffle.store.get(1).unwrap().unwrap();
+        let result = raffle.evaluate_ticket("1").unwrap();
+        assert_eq!(result.lines, ticket.outcomes(&RuleSet::default()));
+        raffle.rebuild().unwrap();
+        assert_eq!(
+            raffle.get_ticket("1").unwrap().total_score,
+            before.total_score
+        );
 
-        assert!(raffle.evaluate_ticket(2).is_err())
+        // The old version is kept until no Ticket is pinned to it anymore
+        assert_eq!(raffle.store.rulesets().unwrap().len(), 2);
+        let empty = Raffle::instantiate().export().unwrap();
+        raffle.import(empty, ImportMode::Replace).unwrap();
+        let kept: Vec<RulesetPin> = raffle
+            .store
+            .rulesets()
+            .unwrap()
+            .iter()
+            .map(RulesetVersion::pin)
+            .collect();
+        assert_eq!(kept, vec![classic_pin(2).unwrap()]);
     }
 
     #[test]
-    fn Raffle_get_ticket_list_type() {
-        let raffle = Raffle::instantiate();
-        assert!(raffle.get_ticket_list().is_array())
+    fn Raffle_rulesets_unpinned() {
+        let mut raffle = Raffle::instantiate();
+        let ticket = static_ticket!();
+        raffle.store.insert(1, ticket.clone()).unwrap();
+
+        // Tickets from before rules were versioned are scored by the first version
+        // kept, which are the rules the Raffle was first told to use
+        raffle.set_games(unpaid_games()).unwrap();
+        raffle.set_games(Games::default()).unwrap();
+        let view = raffle.get_ticket("1").unwrap();
+        assert_eq!(view.rules, classic_pin(1));
+        assert_eq!(view.total_score, 0);
+        assert_ne!(ticket.total_score(&RuleSet::default()), 0);
+        assert_eq!(raffle.game(None).unwrap().pin(), classic_pin(2).unwrap());
     }
 
     #[test]
-    fn Raffle_get_ticket_list_composition() {
+    fn Raffle_rulesets_transfer() {
         let mut raffle = Raffle::instantiate();
-        let ticket = static_ticket!();
-        let idx = 1;
-        assert!(raffle.tickets.insert(idx, ticket).is_none());
-        let output: Vec<u8> = LINE_EVAL_VALUES.iter().map(|i| *i).collect();
+        raffle.new_ticket(Some(3), None, None).unwrap();
+        raffle.set_games(unpaid_games()).unwrap();
+        raffle.new_ticket(Some(3), None, None).unwrap();
+
+        // Restoring brings back every version exactly as it was
+        let mut restored = Raffle::instantiate();
+        restored.set_games(unpaid_games()).unwrap();
+        restored.restore(raffle.snapshot().unwrap()).unwrap();
+        for id in &["1", "2"] {
+            assert_eq!(
+                restored.get_ticket(id).unwrap(),
+                raffle.get_ticket(id).unwrap()
+            );
+        }
 
+        // Importing pins Tickets to whichever version holds the same rules here
+        let mut imported = Raffle::instantiate();
+        imported.set_games(unpaid_games()).unwrap();
+        imported
+            .import(raffle.export().unwrap(), ImportMode::Replace)
+            .unwrap();
+        let (first, second) = (
+            imported.get_ticket("1").unwrap(),
+            imported.get_ticket("2").unwrap(),
+        );
+        assert_eq!(first.rules, classic_pin(2));
         assert_eq!(
-            raffle.get_ticket_list(),
-            json!([{"id": idx, "lines": output}])
+            first.total_score,
+            raffle.get_ticket("1").unwrap().total_score
         );
+        assert_eq!(second.rules, classic_pin(1));
+
+        let mut json = serde_json::to_value(raffle.export().unwrap()).unwrap();
+        json["rulesets"] = json!([]);
+        match imported.import(serde_json::from_value(json).unwrap(), ImportMode::Replace) {
+            Err(ErrorKind::InvalidImport(problems)) => assert_eq!(
+                problems[0],
+                "ticket 1 is pinned to ruleset classic version 1 which isn't exported"
+            ),
+            other => panic!("expected InvalidImport, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn Raffle_rulesets_journaled() {
+        let classic = |rules: &RuleSet| RulesetVersion {
+            id: String::from(CLASSIC_GAME),
+            version: 1,
+            rules: rules.definition().to_vec(),
+        };
+
+        // A journaled version replaces one nothing is pinned to yet, after which
+        // the current rules are numbered again
+        let mut raffle = Raffle::instantiate();
+        raffle.set_games(unpaid_games()).unwrap();
+        raffle.replay_ruleset(classic(&RuleSet::default())).unwrap();
+        assert_eq!(raffle.game(None).unwrap().pin(), classic_pin(2).unwrap());
+        raffle.new_ticket(Some(3), None, None).unwrap();
+        assert_eq!(raffle.get_ticket("1").unwrap().rules, classic_pin(2));
+
+        // ...but one a Ticket is pinned to is never redefined
+        let mut pinned = Raffle::instantiate();
+        pinned.new_ticket(Some(3), None, None).unwrap();
+        match pinned.replay_ruleset(classic(&RuleSet::new(Vec::new()))) {
+            Err(ErrorKind::RulesetConflict(pin)) => assert_eq!(Some(pin), classic_pin(1)),
+            other => panic!("expected RulesetConflict, got {:?}", other),
+        }
+        pinned.replay_ruleset(classic(&RuleSet::default())).unwrap();
+        assert_eq!(pinned.store.rulesets().unwrap().len(), 1);
     }
 
     // Test data
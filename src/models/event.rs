@@ -0,0 +1,137 @@
+use {
+    super::{classic_game, ErrorKind, Games, Line, RulesetPin, State, StatusResult, Ticket},
+    serde::{Deserialize, Serialize},
+};
+
+// A single thing that happened to one of a Raffle's Tickets. Events are the source
+// of truth, the stored Tickets are whatever folding every Event in order produces
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Event {
+    // Position in the Raffle's event stream, starting at 1
+    pub seq: u64,
+    // Key of the Ticket it happened to
+    pub ticket: u64,
+    // When it happened, in seconds since the unix epoch
+    pub at: u64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+// What happened, along with everything needed to apply it again later
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    TicketCreated {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        public_id: Option<String>,
+        #[serde(default = "classic_game")]
+        game: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        rules: Option<RulesetPin>,
+        lines: Vec<Line>,
+        requester: Option<String>,
+    },
+    LinesAppended {
+        lines: Vec<Line>,
+        requester: Option<String>,
+    },
+    TicketChecked {
+        score: u64,
+        message: String,
+    },
+    TicketVoided,
+    TicketExpired,
+    // Brought in whole by an import, or stored before Events were recorded
+    TicketImported {
+        ticket: Ticket,
+    },
+    TicketRemoved,
+}
+
+impl Event {
+    // Folds the Event into its Ticket as it was before, returning the Ticket as it is
+    // after or None if it no longer exists. Checked Tickets have their Lines scored by
+    // the version of their Game's rules in [games] they are pinned to. Returns an error
+    // if the Event can't happen to that Ticket, without recording anything
+    pub(super) fn apply(
+        &self,
+        ticket: Option<Ticket>,
+        games: &Games,
+    ) -> Result<Option<Ticket>, ErrorKind> {
+        let mut ticket = match (&self.kind, ticket) {
+            (
+                EventKind::TicketCreated {
+                    public_id,
+                    game,
+                    rules,
+                    lines,
+                    requester,
+                },
+                _,
+            ) => {
+                let mut ticket = Ticket::created(lines.clone(), requester.clone(), self.at);
+                ticket.public_id = public_id.clone();
+                ticket.game = game.clone();
+                ticket.rules = rules.clone();
+                return Ok(Some(ticket));
+            }
+            (EventKind::TicketImported { ticket }, _) => return Ok(Some(ticket.clone())),
+            (EventKind::TicketRemoved, _) => return Ok(None),
+            (_, Some(ticket)) => ticket,
+            (_, None) => return Err(ErrorKind::TicketNotFound(self.ticket.to_string())),
+        };
+
+        let id = ticket.id(self.ticket);
+        match &self.kind {
+            EventKind::LinesAppended { lines, requester } => {
+                ticket.transition(&id, State::Amended, self.at)?;
+                ticket.append(lines.clone(), requester.clone(), self.at)
+            }
+            EventKind::TicketChecked { score, message } => {
+                ticket.transition(&id, State::Checked, self.at)?;
+                ticket.result = Some(StatusResult {
+                    lines: ticket.outcomes(games.rules_of(&ticket)),
+                    id,
+                    score: *score,
+                    message: message.clone(),
+                })
+            }
+            EventKind::TicketVoided => ticket.transition(&id, State::Voided, self.at)?,
+            EventKind::TicketExpired => ticket.transition(&id, State::Expired, self.at)?,
+            _ => (),
+        }
+
+        Ok(Some(ticket))
+    }
+}
+
+// An Event as listed in a Ticket's history, without the key the Ticket is stored under
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryEntry {
+    pub seq: u64,
+    pub at: u64,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+impl From<Event> for HistoryEntry {
+    fn from(event: Event) -> Self {
+        HistoryEntry {
+            seq: event.seq,
+            at: event.at,
+            kind: event.kind,
+        }
+    }
+}
+
+// Everything that happened to a Ticket, oldest first
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TicketHistory {
+    pub id: String,
+    pub events: Vec<HistoryEntry>,
+}
+
+// A read model built by folding every Event of a Raffle in order, see Raffle::project
+pub trait Projection {
+    fn apply(&mut self, event: &Event) -> Result<(), ErrorKind>;
+}
@@ -0,0 +1,621 @@
+use {
+    super::{ErrorKind, LineShape, RuleDef, RuleSet, RuleView, Ticket, DEFAULT_LINES},
+    serde::{Deserialize, Serialize},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        fs,
+        path::Path,
+    },
+};
+
+// A variant of the raffle: how its Lines are drawn and scored, and how many
+// Lines a Ticket gets when the user doesn't say
+pub struct Game {
+    pub name: String,
+    pub rules: RuleSet,
+    pub shape: LineShape,
+    pub default_lines: u64,
+    // Which version of the Game's rules [rules] are, see Games::keep
+    version: u32,
+}
+
+impl Game {
+    // The classic game, 10 Lines of 3 numbers between 0 and 2 scored by RuleSet::default
+    pub fn classic() -> Self {
+        Game {
+            name: String::from(CLASSIC_GAME),
+            rules: RuleSet::default(),
+            shape: LineShape::default(),
+            default_lines: DEFAULT_LINES,
+            version: 0,
+        }
+    }
+
+    // The version of the Game's rules new Tickets are scored by
+    pub(super) fn pin(&self) -> RulesetPin {
+        RulesetPin {
+            id: self.name.clone(),
+            version: self.version,
+        }
+    }
+
+    fn view(&self, default: bool) -> GameView {
+        GameView {
+            name: self.name.clone(),
+            default,
+            version: self.version,
+            default_lines: self.default_lines,
+            line_width: self.shape.width,
+            line_symbols: self.shape.symbols,
+            rules: self.rules.describe(),
+        }
+    }
+}
+
+// Name of the Game served when no games are configured, and of the one
+// Tickets from before there were games belong to
+pub const CLASSIC_GAME: &str = "classic";
+
+// A Game as returned to clients
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GameView {
+    pub name: String,
+    // Whether Tickets are created in this Game when the user doesn't name one
+    pub default: bool,
+    // Version of the rules new Tickets in this Game are scored by
+    pub version: u32,
+    pub default_lines: u64,
+    pub line_width: usize,
+    pub line_symbols: u8,
+    rules: Vec<RuleView>,
+}
+
+// Which version of which Game's rules a Ticket is scored by, the id is the Game's name
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct RulesetPin {
+    pub id: String,
+    pub version: u32,
+}
+
+// A version of a Game's rules as written down, kept for as long as a Ticket is
+// pinned to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RulesetVersion {
+    pub id: String,
+    pub version: u32,
+    pub rules: Vec<RuleDef>,
+}
+
+impl RulesetVersion {
+    pub fn pin(&self) -> RulesetPin {
+        RulesetPin {
+            id: self.id.clone(),
+            version: self.version,
+        }
+    }
+}
+
+// The Games a Raffle serves side by side, Tickets are created in the [default]
+// one unless the user names another
+pub struct Games {
+    games: Vec<Game>,
+    default: usize,
+    // Every version of every Game's rules a Ticket may be scored by, including
+    // the current ones
+    versions: BTreeMap<RulesetPin, RuleSet>,
+}
+
+impl Games {
+    // Serves nothing but [game]
+    pub fn single(game: Game) -> Self {
+        Games::with(vec![game], 0)
+    }
+
+    // Serves [games], each with its rules as version 1 until told what was kept before
+    fn with(games: Vec<Game>, default: usize) -> Self {
+        let mut games = Games {
+            games,
+            default,
+            versions: BTreeMap::new(),
+        };
+        games.keep(Vec::new());
+        games
+    }
+
+    // Loads the Games described by the TOML file at [path], see Games::from_toml
+    pub fn load(path: &Path) -> Result<Self, ErrorKind> {
+        match fs::read_to_string(path) {
+            Ok(text) => Games::from_toml(&text),
+            Err(e) => Err(ErrorKind::InvalidGames(vec![format!(
+                "unable to read {}: {}",
+                path.display(),
+                e
+            )])),
+        }
+    }
+
+    // Builds the Games from a TOML document listing each as a [[game]] table with a
+    // name and optionally a rules_path, line_width, line_symbols and default_lines,
+    // which fall back to the classic game's. The game named by the top level default
+    // is the default, otherwise the first one listed.
+    // Returns every problem found if the document doesn't describe usable Games
+    pub fn from_toml(text: &str) -> Result<Self, ErrorKind> {
+        let config: GamesConfig = match toml::from_str(text) {
+            Ok(config) => config,
+            Err(e) => return Err(ErrorKind::InvalidGames(vec![e.to_string()])),
+        };
+
+        let mut problems = Vec::new();
+        if config.game.is_empty() {
+            problems.push(String::from("no games are defined"));
+        }
+        let mut names = BTreeSet::new();
+        let mut games = Vec::new();
+        for entry in config.game {
+            if entry.name.is_empty() {
+                problems.push(String::from("a game has an empty name"));
+            }
+            if !names.insert(entry.name.clone()) {
+                problems.push(format!("game '{}' is defined more than once", entry.name));
+            }
+            match entry.into_game() {
+                Ok(game) => games.push(game),
+                Err(mut found) => problems.append(&mut found),
+            }
+        }
+        let default = match &config.default {
+            Some(name) => match games.iter().position(|game| &game.name == name) {
+                Some(default) => default,
+                None => {
+                    problems.push(format!("the default game '{}' isn't defined", name));
+                    0
+                }
+            },
+            None => 0,
+        };
+
+        match problems.is_empty() {
+            true => Ok(Games::with(games, default)),
+            false => Err(ErrorKind::InvalidGames(problems)),
+        }
+    }
+
+    // The Game called [name], or the default Game if no name is given
+    pub fn get(&self, name: Option<&str>) -> Result<&Game, ErrorKind> {
+        match name {
+            Some(name) => self
+                .find(name)
+                .ok_or_else(|| ErrorKind::UnknownGame(String::from(name))),
+            None => Ok(&self.games[self.default]),
+        }
+    }
+
+    // Every Game, in the order they were listed
+    pub fn describe(&self) -> Vec<GameView> {
+        self.games
+            .iter()
+            .enumerate()
+            .map(|(index, game)| game.view(index == self.default))
+            .collect()
+    }
+
+    // Takes over every version of the rules [kept] so far and numbers each Game's
+    // current rules: the same version as the latest kept one if they are the same,
+    // otherwise the version after it. Returns the versions that weren't kept before
+    pub(super) fn keep(&mut self, kept: Vec<RulesetVersion>) -> Vec<RulesetVersion> {
+        self.versions = kept
+            .into_iter()
+            .map(|kept| (kept.pin(), RuleSet::new(kept.rules)))
+            .collect();
+
+        let mut added = Vec::new();
+        for game in &mut self.games {
+            let latest = self
+                .versions
+                .keys()
+                .rev()
+                .find(|pin| pin.id == game.name)
+                .cloned();
+            game.version = match latest {
+                Some(pin) if self.versions[&pin].definition() == game.rules.definition() => {
+                    pin.version
+                }
+                latest => {
+                    let version = latest.map_or(1, |pin| pin.version + 1);
+                    let rules = game.rules.definition().to_vec();
+                    self.versions.insert(
+                        RulesetPin {
+                            id: game.name.clone(),
+                            version,
+                        },
+                        RuleSet::new(rules.clone()),
+                    );
+                    added.push(RulesetVersion {
+                        id: game.name.clone(),
+                        version,
+                        rules,
+                    });
+                    version
+                }
+            };
+        }
+        added
+    }
+
+    // Takes over a version of the rules brought in from elsewhere, returning the
+    // version kept here with the same rules, or the one it was kept as if there is
+    // none yet along with the version to write down
+    pub(super) fn adopt(
+        &mut self,
+        version: RulesetVersion,
+    ) -> (RulesetPin, Option<RulesetVersion>) {
+        let same = self
+            .versions
+            .iter()
+            .find(|(pin, rules)| pin.id == version.id && rules.definition() == &version.rules[..])
+            .map(|(pin, _)| pin.clone());
+        if let Some(pin) = same {
+            return (pin, None);
+        }
+
+        let pin = RulesetPin {
+            version: self
+                .versions
+                .keys()
+                .rev()
+                .find(|pin| pin.id == version.id)
+                .map_or(1, |pin| pin.version + 1),
+            id: version.id,
+        };
+        self.versions
+            .insert(pin.clone(), RuleSet::new(version.rules.clone()));
+        let adopted = RulesetVersion {
+            id: pin.id.clone(),
+            version: pin.version,
+            rules: version.rules,
+        };
+        (pin, Some(adopted))
+    }
+
+    // Forgets every version of the rules that isn't [referenced] or current,
+    // returning which were forgotten
+    pub(super) fn prune(&mut self, referenced: &BTreeSet<RulesetPin>) -> Vec<RulesetPin> {
+        let current: BTreeSet<RulesetPin> = self.games.iter().map(Game::pin).collect();
+        let unused: Vec<RulesetPin> = self
+            .versions
+            .keys()
+            .filter(|pin| !referenced.contains(pin) && !current.contains(pin))
+            .cloned()
+            .collect();
+        for pin in &unused {
+            self.versions.remove(pin);
+        }
+        unused
+    }
+
+    // Every version of the rules kept
+    pub(super) fn versions(&self) -> Vec<RulesetVersion> {
+        self.versions
+            .iter()
+            .map(|(pin, rules)| RulesetVersion {
+                id: pin.id.clone(),
+                version: pin.version,
+                rules: rules.definition().to_vec(),
+            })
+            .collect()
+    }
+
+    // The version of the rules [ticket] is scored by: the one it was pinned to when
+    // it was created, or for Tickets from before rules were versioned the first kept
+    // version of its Game's rules
+    pub(super) fn pin_of(&self, ticket: &Ticket) -> Option<RulesetPin> {
+        match &ticket.rules {
+            Some(pin) => Some(pin.clone()),
+            None => {
+                let game = self.of(ticket);
+                self.versions
+                    .keys()
+                    .find(|pin| pin.id == game.name)
+                    .cloned()
+            }
+        }
+    }
+
+    // The rules [ticket] is scored by, see Games::pin_of
+    pub(super) fn rules_of(&self, ticket: &Ticket) -> &RuleSet {
+        match self.pin_of(ticket).and_then(|pin| self.versions.get(&pin)) {
+            Some(rules) => rules,
+            None => &self.of(ticket).rules,
+        }
+    }
+
+    // The Game [ticket] belongs to, Tickets whose Game is no longer served
+    // fall back to the default Game
+    pub(super) fn of(&self, ticket: &Ticket) -> &Game {
+        self.find(&ticket.game).unwrap_or(&self.games[self.default])
+    }
+
+    // The Game called [name]. Tickets from before there were games belong to the
+    // classic game, which is the default Game if no Game is called that
+    pub(super) fn find(&self, name: &str) -> Option<&Game> {
+        match self.games.iter().find(|game| game.name == name) {
+            None if name == CLASSIC_GAME => Some(&self.games[self.default]),
+            game => game,
+        }
+    }
+}
+
+impl Default for Games {
+    fn default() -> Self {
+        Games::single(Game::classic())
+    }
+}
+
+// The Games as written in their TOML file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GamesConfig {
+    default: Option<String>,
+    #[serde(default)]
+    game: Vec<GameEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct GameEntry {
+    name: String,
+    rules_path: Option<String>,
+    line_width: Option<usize>,
+    line_symbols: Option<u8>,
+    default_lines: Option<u64>,
+}
+
+impl GameEntry {
+    // Loads the Game's rules and checks its settings, returning every problem found
+    fn into_game(self) -> Result<Game, Vec<String>> {
+        let classic = Game::classic();
+        let mut problems = Vec::new();
+
+        let rules = match &self.rules_path {
+            Some(path) => RuleSet::load(Path::new(path)).map_err(|e| match e {
+                ErrorKind::InvalidRules(found) => found,
+                e => vec![e.to_string()],
+            }),
+            None => Ok(classic.rules),
+        };
+        let shape = LineShape::new(
+            self.line_width.unwrap_or(classic.shape.width),
+            self.line_symbols.unwrap_or(classic.shape.symbols),
+        )
+        .map_err(|e| match e {
+            ErrorKind::InvalidShape(reason) => vec![reason],
+            e => vec![e.to_string()],
+        });
+        let default_lines = self.default_lines.unwrap_or(classic.default_lines);
+        if default_lines == 0 {
+            problems.push(format!("game '{}' has 0 default lines", self.name));
+        }
+
+        match (rules, shape, problems.is_empty()) {
+            (Ok(rules), Ok(shape), true) => Ok(Game {
+                name: self.name,
+                rules,
+                shape,
+                default_lines,
+                version: 0,
+            }),
+            (rules, shape, _) => {
+                let name = &self.name;
+                problems.extend(
+                    rules
+                        .err()
+                        .into_iter()
+                        .chain(shape.err())
+                        .flatten()
+                        .map(|problem| format!("game '{}' {}", name, problem)),
+                );
+                Err(problems)
+            }
+        }
+    }
+}
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+
+    const CLASSIC_AND_WIDE: &str = r#"
+        default = "wide"
+
+        [[game]]
+        name = "classic"
+
+        [[game]]
+        name = "wide"
+        line_width = 5
+        line_symbols = 4
+        default_lines = 3
+    "#;
+
+    #[test]
+    fn Games_from_toml() {
+        let games = Games::from_toml(CLASSIC_AND_WIDE).unwrap();
+
+        let wide = games.get(None).unwrap();
+        assert_eq!(wide.name, "wide");
+        assert_eq!(wide.shape, LineShape::new(5, 4).unwrap());
+        assert_eq!(wide.default_lines, 3);
+
+        let classic = games.get(Some("classic")).unwrap();
+        assert_eq!(classic.shape, LineShape::default());
+        assert_eq!(classic.default_lines, DEFAULT_LINES);
+        assert_eq!(classic.rules.outcomes(), RuleSet::default().outcomes());
+
+        match games.get(Some("missing")) {
+            Err(ErrorKind::UnknownGame(name)) => assert_eq!(name, "missing"),
+            _ => panic!("expected UnknownGame"),
+        }
+    }
+
+    #[test]
+    fn Games_find() {
+        let games = Games::from_toml(CLASSIC_AND_WIDE).unwrap();
+        assert_eq!(games.find("classic").unwrap().name, "classic");
+
+        // Without a classic game, Tickets from before there were games fall to the default
+        let games = Games::from_toml("[[game]]\nname = \"wide\"\nline_width = 5\n").unwrap();
+        assert_eq!(games.find(CLASSIC_GAME).unwrap().name, "wide");
+        assert!(games.find("missing").is_none());
+    }
+
+    #[test]
+    fn Games_describe() {
+        let games = Games::from_toml(CLASSIC_AND_WIDE).unwrap();
+        let views = games.describe();
+
+        assert_eq!(
+            views.iter().map(|view| view.default).collect::<Vec<bool>>(),
+            vec![false, true]
+        );
+        assert_eq!(views[1].line_width, 5);
+        assert_eq!(views[0].rules, RuleSet::default().describe());
+    }
+
+    // The classic rules with a different payout
+    fn richer_classic() -> RuleSet {
+        RuleSet::from_toml(
+            r#"
+            [[rule]]
+            name = "sum of two"
+            priority = 1
+            payout = 20
+            condition = { kind = "sum_equals", sum = 2 }
+            "#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn Games_keep() {
+        let games = Games::default();
+        assert_eq!(games.get(None).unwrap().pin().version, 1);
+        let kept = games.versions();
+        assert_eq!(kept.len(), 1);
+
+        // The same rules keep their version
+        let mut games = Games::default();
+        assert!(games.keep(kept.clone()).is_empty());
+        assert_eq!(games.get(None).unwrap().version, 1);
+
+        // Changed rules get the next version, the old one is kept alongside
+        let mut classic = Game::classic();
+        classic.rules = richer_classic();
+        let mut games = Games::single(classic);
+        let added = games.keep(kept);
+        assert_eq!(added.len(), 1);
+        assert_eq!(
+            added[0].pin(),
+            RulesetPin {
+                id: String::from(CLASSIC_GAME),
+                version: 2
+            }
+        );
+        assert_eq!(games.versions().len(), 2);
+    }
+
+    #[test]
+    fn Games_rules_of() {
+        let mut classic = Game::classic();
+        classic.rules = richer_classic();
+        let mut games = Games::single(classic);
+        games.keep(Games::default().versions());
+
+        // Tickets are scored by the rules they were pinned to, unpinned Tickets by
+        // the first version kept
+        let mut ticket = Ticket::from(1);
+        let first = games.rules_of(&ticket).definition().to_vec();
+        assert_eq!(first, RuleSet::default().definition());
+        ticket.rules = Some(games.get(None).unwrap().pin());
+        assert_eq!(
+            games.rules_of(&ticket).definition(),
+            richer_classic().definition()
+        );
+    }
+
+    #[test]
+    fn Games_adopt_prune() {
+        let mut games = Games::default();
+        let kept = games.versions().remove(0);
+
+        // Rules already kept are adopted as the version they are kept as
+        let mut foreign = kept.clone();
+        foreign.version = 7;
+        assert_eq!(games.adopt(foreign), (kept.pin(), None));
+
+        let mut foreign = RulesetVersion {
+            id: String::from(CLASSIC_GAME),
+            version: 1,
+            rules: richer_classic().definition().to_vec(),
+        };
+        let (pin, added) = games.adopt(foreign.clone());
+        assert_eq!(pin.version, 2);
+        foreign.version = 2;
+        assert_eq!(added, Some(foreign));
+
+        // Current rules are never pruned, others only once nothing references them
+        let referenced: BTreeSet<RulesetPin> = vec![pin.clone()].into_iter().collect();
+        assert!(games.prune(&referenced).is_empty());
+        assert_eq!(games.prune(&BTreeSet::new()), vec![pin]);
+        assert_eq!(games.versions(), vec![kept]);
+    }
+
+    #[test]
+    fn Games_load() {
+        // The games file shipped alongside Rocket.toml
+        let games = Games::load(Path::new("games.toml")).unwrap();
+        assert_eq!(games.get(None).unwrap().name, CLASSIC_GAME);
+        assert!(games.describe().len() > 1);
+
+        assert!(Games::load(Path::new("missing.toml")).is_err());
+    }
+
+    #[test]
+    fn Games_from_toml_invalid() {
+        let problems = |text: &str| match Games::from_toml(text) {
+            Err(ErrorKind::InvalidGames(problems)) => problems,
+            Err(e) => panic!("expected InvalidGames, got {:?}", e),
+            Ok(_) => panic!("expected InvalidGames"),
+        };
+
+        assert_eq!(problems(""), vec!["no games are defined"]);
+        let found = problems(
+            r#"
+                default = "missing"
+
+                [[game]]
+                name = "classic"
+                default_lines = 0
+
+                [[game]]
+                name = "classic"
+                line_width = 0
+                rules_path = "missing.toml"
+                "#,
+        );
+        assert_eq!(found.len(), 5);
+        assert_eq!(found[0], "game 'classic' has 0 default lines");
+        assert_eq!(found[1], "game 'classic' is defined more than once");
+        assert!(found[2].starts_with("game 'classic' unable to read missing.toml"));
+        assert!(found[3].ends_with("not 0 out of 3"));
+        assert_eq!(found[4], "the default game 'missing' isn't defined");
+        // Misspelt fields are caught while parsing
+        assert_eq!(problems("[[game]]\nname = \"x\"\nlines = 4\n").len(), 1);
+    }
+}
@@ -0,0 +1,19 @@
+use super::{ErrorKind, Event, RulesetVersion};
+
+// Durable log the Raffle writes each Event to before acknowledging it,
+// so anything that happened since the last Snapshot can be replayed after a crash
+pub trait Journal: Send + Sync {
+    // Appends [event], only returning once it is safely stored
+    fn record(&mut self, event: &Event) -> Result<(), ErrorKind>;
+
+    // Appends a new [version] of a Game's rules, only returning once it is safely
+    // stored, so no Ticket is ever pinned to a version a crash would lose
+    fn record_ruleset(&mut self, version: &RulesetVersion) -> Result<(), ErrorKind>;
+
+    // Drops the last record again, called when what it records couldn't be stored
+    // after all, so it is never replayed
+    fn rollback(&mut self) -> Result<(), ErrorKind>;
+
+    // Drops every Event, called once a Snapshot covers all of them
+    fn truncate(&mut self) -> Result<(), ErrorKind>;
+}
@@ -0,0 +1,528 @@
+use {
+    super::{ErrorKind, Line},
+    serde::{Deserialize, Serialize},
+    std::{collections::BTreeSet, fs, path::Path},
+};
+
+// A way for a Line to pay out: which Lines it applies to and what they are worth
+pub trait Rule: Send + Sync {
+    // Whether [line] satisfies the Rule
+    fn matches(&self, line: &Line) -> bool;
+
+    // What a Line satisfying the Rule is worth
+    fn payout(&self) -> u8;
+
+    // Which Lines satisfy the Rule, in words
+    fn describe(&self) -> String;
+}
+
+// Rules in priority order, a Line is worth the payout of the first Rule it
+// satisfies, or nothing if it satisfies none of them. The RuleSet keeps the
+// definitions it was built from, so it can be written down and built again later
+pub struct RuleSet {
+    rules: Vec<Box<dyn Rule>>,
+    definition: Vec<RuleDef>,
+}
+
+impl RuleSet {
+    // Builds the Rules of [definition], which is in priority order
+    pub fn new(definition: Vec<RuleDef>) -> Self {
+        RuleSet {
+            rules: definition
+                .iter()
+                .map(|rule| rule.condition.clone().into_rule(rule.payout))
+                .collect(),
+            definition,
+        }
+    }
+
+    // The definitions of every Rule, in priority order
+    pub fn definition(&self) -> &[RuleDef] {
+        &self.definition
+    }
+
+    // Loads the RuleSet described by the TOML file at [path], see RuleSet::from_toml
+    pub fn load(path: &Path) -> Result<Self, ErrorKind> {
+        match fs::read_to_string(path) {
+            Ok(text) => RuleSet::from_toml(&text),
+            Err(e) => Err(ErrorKind::InvalidRules(vec![format!(
+                "unable to read {}: {}",
+                path.display(),
+                e
+            )])),
+        }
+    }
+
+    // Builds a RuleSet from a TOML document listing each Rule as a [[rule]] table with
+    // a name, priority, payout and condition. Rules are tried lowest priority first.
+    // Returns every problem found if the document doesn't describe a usable RuleSet
+    pub fn from_toml(text: &str) -> Result<Self, ErrorKind> {
+        let config: RuleConfig = match toml::from_str(text) {
+            Ok(config) => config,
+            Err(e) => return Err(ErrorKind::InvalidRules(vec![e.to_string()])),
+        };
+
+        let mut problems = Vec::new();
+        if config.rule.is_empty() {
+            problems.push(String::from("no rules are defined"));
+        }
+        let (mut names, mut priorities) = (BTreeSet::new(), BTreeSet::new());
+        for rule in &config.rule {
+            if !names.insert(&rule.name) {
+                problems.push(format!("rule '{}' is defined more than once", rule.name));
+            }
+            if !priorities.insert(rule.priority) {
+                problems.push(format!(
+                    "rule '{}' shares priority {} with another rule",
+                    rule.name, rule.priority
+                ));
+            }
+            if let Err(problem) = rule.condition.check() {
+                problems.push(format!("rule '{}' {}", rule.name, problem));
+            }
+        }
+        if !problems.is_empty() {
+            return Err(ErrorKind::InvalidRules(problems));
+        }
+
+        let mut rules = config.rule;
+        rules.sort_by_key(|rule| rule.priority);
+        Ok(RuleSet::new(
+            rules
+                .into_iter()
+                .map(|rule| RuleDef {
+                    name: rule.name,
+                    payout: rule.payout,
+                    condition: rule.condition,
+                })
+                .collect(),
+        ))
+    }
+
+    // Computes what [line] is worth
+    pub fn evaluate(&self, line: &Line) -> u8 {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(line))
+            .map_or(0, |rule| rule.payout())
+    }
+
+    // Every Rule in priority order, as shown to clients
+    pub fn describe(&self) -> Vec<RuleView> {
+        self.rules
+            .iter()
+            .map(|rule| RuleView {
+                condition: rule.describe(),
+                payout: rule.payout(),
+            })
+            .collect()
+    }
+
+    // Every possible Line outcome, ordered from best to worst
+    pub fn outcomes(&self) -> Vec<u8> {
+        let mut outcomes: Vec<u8> = self.rules.iter().map(|rule| rule.payout()).collect();
+        outcomes.push(0);
+        outcomes.sort_unstable_by(|a, b| b.cmp(a));
+        outcomes.dedup();
+        outcomes
+    }
+}
+
+impl Default for RuleSet {
+    // The classic game, the same as the rules.toml shipped with it. Ordered by
+    // priority, so ex. (2,0,0) which satisfies both |x+y+z == 2| & |x!=y && x!=z|
+    // pays out 10, and a Line satisfying none of them pays out nothing
+    fn default() -> Self {
+        RuleSet::new(vec![
+            RuleDef::new("sum of two", 10, Condition::SumEquals { sum: 2 }),
+            RuleDef::new("all the same", 5, Condition::AllEqual),
+            RuleDef::new("odd one out", 1, Condition::FirstDiffers),
+        ])
+    }
+}
+
+// A single Rule as written down: its name, what it pays out and when
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleDef {
+    name: String,
+    payout: u8,
+    condition: Condition,
+}
+
+impl RuleDef {
+    fn new(name: &str, payout: u8, condition: Condition) -> Self {
+        RuleDef {
+            name: String::from(name),
+            payout,
+            condition,
+        }
+    }
+}
+
+// A Rule as returned to clients
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuleView {
+    pub condition: String,
+    pub payout: u8,
+}
+
+// A RuleSet as written in its TOML file
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleConfig {
+    #[serde(default)]
+    rule: Vec<RuleEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RuleEntry {
+    name: String,
+    priority: u32,
+    payout: u8,
+    condition: Condition,
+}
+
+// The primitives a configured Rule's condition can be, written as
+// ex. condition = { kind = "count_at_least", value = 2, count = 2 }
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+enum Condition {
+    SumEquals { sum: u8 },
+    AllEqual,
+    FirstDiffers,
+    CountAtLeast { value: u8, count: usize },
+    Always,
+}
+
+impl Condition {
+    // Rejects conditions which are almost certainly a mistake
+    fn check(&self) -> Result<(), String> {
+        match self {
+            Condition::CountAtLeast { count: 0, .. } => Err(String::from(
+                "counts at least 0 of a value, use kind = \"always\"",
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn into_rule(self, payout: u8) -> Box<dyn Rule> {
+        match self {
+            Condition::SumEquals { sum } => Box::new(SumIs { sum, payout }),
+            Condition::AllEqual => Box::new(AllEqual { payout }),
+            Condition::FirstDiffers => Box::new(FirstDiffers { payout }),
+            Condition::CountAtLeast { value, count } => Box::new(CountAtLeast {
+                value,
+                count,
+                payout,
+            }),
+            Condition::Always => Box::new(Always { payout }),
+        }
+    }
+}
+
+// The numbers of a Line add up to [sum]
+pub struct SumIs {
+    pub sum: u8,
+    pub payout: u8,
+}
+
+impl Rule for SumIs {
+    fn matches(&self, line: &Line) -> bool {
+        line.numbers().iter().map(|n| *n as u64).sum::<u64>() == self.sum as u64
+    }
+
+    fn payout(&self) -> u8 {
+        self.payout
+    }
+
+    fn describe(&self) -> String {
+        format!("the numbers add up to {}", self.sum)
+    }
+}
+
+// Every number of a Line is the same
+pub struct AllEqual {
+    pub payout: u8,
+}
+
+impl Rule for AllEqual {
+    fn matches(&self, line: &Line) -> bool {
+        match line.numbers().split_first() {
+            Some((first, rest)) => rest.iter().all(|n| n == first),
+            None => false,
+        }
+    }
+
+    fn payout(&self) -> u8 {
+        self.payout
+    }
+
+    fn describe(&self) -> String {
+        String::from("every number is the same")
+    }
+}
+
+// The first number of a Line differs from every other one
+pub struct FirstDiffers {
+    pub payout: u8,
+}
+
+impl Rule for FirstDiffers {
+    fn matches(&self, line: &Line) -> bool {
+        match line.numbers().split_first() {
+            Some((first, rest)) => rest.iter().all(|n| n != first),
+            None => false,
+        }
+    }
+
+    fn payout(&self) -> u8 {
+        self.payout
+    }
+
+    fn describe(&self) -> String {
+        String::from("no other number is the first one")
+    }
+}
+
+// At least [count] of a Line's numbers are [value]
+pub struct CountAtLeast {
+    pub value: u8,
+    pub count: usize,
+    pub payout: u8,
+}
+
+impl Rule for CountAtLeast {
+    fn matches(&self, line: &Line) -> bool {
+        line.numbers().iter().filter(|n| **n == self.value).count() >= self.count
+    }
+
+    fn payout(&self) -> u8 {
+        self.payout
+    }
+
+    fn describe(&self) -> String {
+        format!("at least {} numbers are {}", self.count, self.value)
+    }
+}
+
+// Any Line at all, ends a RuleSet with a catch-all
+pub struct Always {
+    pub payout: u8,
+}
+
+impl Rule for Always {
+    fn matches(&self, _line: &Line) -> bool {
+        true
+    }
+
+    fn payout(&self) -> u8 {
+        self.payout
+    }
+
+    fn describe(&self) -> String {
+        String::from("any line")
+    }
+}
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+
+    #[test]
+    fn RuleSet_empty_line() {
+        // A Line without numbers satisfies none of the classic rules
+        let empty = Line(Vec::new());
+        assert!(!AllEqual { payout: 5 }.matches(&empty));
+        assert!(!FirstDiffers { payout: 1 }.matches(&empty));
+        assert_eq!(RuleSet::default().evaluate(&empty), 0);
+    }
+
+    #[test]
+    fn RuleSet_priority() {
+        let line = Line::from((2, 0, 0));
+        assert_eq!(RuleSet::default().evaluate(&line), 10);
+
+        // Reordering the same Rules changes what the Line is worth
+        let reordered = RuleSet::new(vec![
+            RuleDef::new("odd one out", 1, Condition::FirstDiffers),
+            RuleDef::new("sum of two", 10, Condition::SumEquals { sum: 2 }),
+        ]);
+        assert_eq!(reordered.evaluate(&line), 1);
+    }
+
+    #[test]
+    fn RuleSet_describe() {
+        let rules = RuleSet::from_toml(
+            r#"
+            [[rule]]
+            name = "two twos"
+            priority = 1
+            payout = 20
+            condition = { kind = "count_at_least", value = 2, count = 2 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(
+            rules.describe(),
+            vec![RuleView {
+                condition: String::from("at least 2 numbers are 2"),
+                payout: 20
+            }]
+        );
+        assert_eq!(
+            RuleSet::default()
+                .describe()
+                .iter()
+                .map(|rule| rule.payout)
+                .collect::<Vec<u8>>(),
+            vec![10, 5, 1]
+        );
+    }
+
+    #[test]
+    fn RuleSet_outcomes() {
+        assert_eq!(RuleSet::default().outcomes(), vec![10, 5, 1, 0]);
+
+        let rules = RuleSet::new(vec![
+            RuleDef::new("all the same", 20, Condition::AllEqual),
+            RuleDef::new("sum of three", 20, Condition::SumEquals { sum: 3 }),
+        ]);
+        assert_eq!(rules.outcomes(), vec![20, 0]);
+        // Lines satisfying no Rule are worth nothing
+        assert_eq!(rules.evaluate(&Line::from((0, 1, 0))), 0);
+    }
+
+    // Matches RuleSet::default
+    const CLASSIC: &str = r#"
+        [[rule]]
+        name = "odd one out"
+        priority = 30
+        payout = 1
+        condition = { kind = "first_differs" }
+
+        [[rule]]
+        name = "sum of two"
+        priority = 10
+        payout = 10
+        condition = { kind = "sum_equals", sum = 2 }
+
+        [[rule]]
+        name = "all the same"
+        priority = 20
+        payout = 5
+        condition = { kind = "all_equal" }
+    "#;
+
+    // Checks [rules] scores every possible Line like RuleSet::default does
+    fn assert_classic(rules: &RuleSet) {
+        let classic = RuleSet::default();
+        (0..27)
+            .map(|n| Line::from((n / 9, n / 3 % 3, n % 3)))
+            .for_each(|line| assert_eq!(rules.evaluate(&line), classic.evaluate(&line)));
+    }
+
+    #[test]
+    fn RuleSet_from_toml() {
+        let rules = RuleSet::from_toml(CLASSIC).unwrap();
+        assert_classic(&rules);
+        assert_eq!(rules.outcomes(), RuleSet::default().outcomes());
+
+        let rules = RuleSet::from_toml(
+            r#"
+            [[rule]]
+            name = "two twos"
+            priority = 1
+            payout = 20
+            condition = { kind = "count_at_least", value = 2, count = 2 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(rules.evaluate(&Line::from((2, 1, 2))), 20);
+        assert_eq!(rules.evaluate(&Line::from((2, 1, 1))), 0);
+    }
+
+    #[test]
+    fn RuleSet_definition() {
+        // The definition builds the same RuleSet again, and survives being written down
+        let rules = RuleSet::from_toml(CLASSIC).unwrap();
+        let json = serde_json::to_string(rules.definition()).unwrap();
+        let rebuilt = RuleSet::new(serde_json::from_str(&json).unwrap());
+        assert_eq!(rebuilt.definition(), rules.definition());
+        assert_classic(&rebuilt);
+
+        assert_eq!(
+            serde_json::to_value(&rules.definition()[0]).unwrap(),
+            serde_json::json!({
+                "name": "sum of two",
+                "payout": 10,
+                "condition": {"kind": "sum_equals", "sum": 2}
+            })
+        );
+        assert_eq!(rules.definition(), RuleSet::default().definition());
+    }
+
+    #[test]
+    fn RuleSet_load() {
+        // The rules file shipped alongside Rocket.toml is the classic game
+        let shipped = RuleSet::load(Path::new("rules.toml")).unwrap();
+        assert_classic(&shipped);
+        assert_eq!(shipped.definition(), RuleSet::default().definition());
+
+        assert!(RuleSet::load(Path::new("missing.toml")).is_err());
+    }
+
+    #[test]
+    fn RuleSet_from_toml_invalid() {
+        let problems = |text: &str| match RuleSet::from_toml(text) {
+            Err(ErrorKind::InvalidRules(problems)) => problems,
+            Err(e) => panic!("expected InvalidRules, got {:?}", e),
+            Ok(_) => panic!("expected InvalidRules"),
+        };
+
+        assert_eq!(problems(""), vec!["no rules are defined"]);
+        assert_eq!(
+            problems(&format!(
+                "{}{}",
+                CLASSIC,
+                r#"
+                [[rule]]
+                name = "sum of two"
+                priority = 20
+                payout = 3
+                condition = { kind = "count_at_least", value = 1, count = 0 }
+                "#
+            )),
+            vec![
+                "rule 'sum of two' is defined more than once",
+                "rule 'sum of two' shares priority 20 with another rule",
+                "rule 'sum of two' counts at least 0 of a value, use kind = \"always\"",
+            ]
+        );
+        // Unknown conditions and misspelt fields are caught while parsing
+        assert_eq!(
+            problems(
+                r#"
+                [[rule]]
+                name = "lucky"
+                priority = 1
+                payout = 7
+                condition = { kind = "prime" }
+                "#
+            )
+            .len(),
+            1
+        );
+        assert_eq!(
+            problems("[[rule]]\nname = \"x\"\npriority = 1\npayout = 1\ncondtion = {}\n").len(),
+            1
+        );
+    }
+}
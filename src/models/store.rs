@@ -0,0 +1,401 @@
+use {
+    super::{ErrorKind, Event, RulesetPin, RulesetVersion, State, Ticket},
+    std::{collections::BTreeMap, ops::Bound},
+};
+
+// Keeps Tickets in an embedded SQLite database
+mod sqlite;
+pub use self::sqlite::SqliteStore;
+
+// Storage backend for a Raffle's Tickets, keyed by their ID, and the Events they
+// were derived from. Backends hand out owned Tickets, the Raffle writes back any
+// change via update
+pub trait TicketStore: Send + Sync {
+    // Reserves an ID that no stored Ticket is using
+    fn allocate_id(&mut self) -> Result<u64, ErrorKind>;
+
+    // Stores a new Ticket under [id]
+    fn insert(&mut self, id: u64, ticket: Ticket) -> Result<(), ErrorKind>;
+
+    // Returns the Ticket stored under [id], if there is one
+    fn get(&self, id: u64) -> Result<Option<Ticket>, ErrorKind>;
+
+    // Returns the ID of the Ticket whose opaque ID is [public_id], if there is one
+    fn find(&self, public_id: &str) -> Result<Option<u64>, ErrorKind>;
+
+    // Replaces the Ticket stored under [id], or returns an error if there is none
+    fn update(&mut self, id: u64, ticket: Ticket) -> Result<(), ErrorKind>;
+
+    // Removes and returns the Ticket stored under [id], if there is one
+    fn remove(&mut self, id: u64) -> Result<Option<Ticket>, ErrorKind>;
+
+    // Returns up to [limit] Tickets in ID order, starting after the ID [after]
+    fn range(
+        &self,
+        after: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<(u64, Ticket)>, ErrorKind>;
+
+    // Number of Tickets stored
+    fn len(&self) -> Result<u64, ErrorKind>;
+
+    // Number of Tickets stored in any of [states]
+    fn count_in(&self, states: &[State]) -> Result<u64, ErrorKind>;
+
+    // Highest ID handed out by allocate_id so far
+    fn last_id(&self) -> Result<u64, ErrorKind>;
+
+    // Moves the ID counter, so allocate_id carries on from [id]
+    fn set_last_id(&mut self, id: u64) -> Result<(), ErrorKind>;
+
+    // Appends [event] to the stored Events, they are never changed once stored
+    fn append_event(&mut self, event: &Event) -> Result<(), ErrorKind>;
+
+    // Appends [event] and stores the Ticket it left behind, as one change: either both
+    // are kept or neither is. If there is none the Ticket is removed along with every
+    // one of its Events, so removed Tickets don't linger in the event stream
+    fn commit(&mut self, event: &Event, ticket: Option<Ticket>) -> Result<(), ErrorKind>;
+
+    // Returns every stored Event in order, or only those of the Ticket stored under [id]
+    fn events(&self, id: Option<u64>) -> Result<Vec<Event>, ErrorKind>;
+
+    // Sequence number of the last stored Event, 0 if there are none. Events pruned
+    // along with their Ticket still count
+    fn last_seq(&self) -> Result<u64, ErrorKind>;
+
+    // Moves the Event counter, so last_seq carries on from [seq]
+    fn set_last_seq(&mut self, seq: u64) -> Result<(), ErrorKind>;
+
+    // Returns every stored version of the rules, ordered by ruleset and version
+    fn rulesets(&self) -> Result<Vec<RulesetVersion>, ErrorKind>;
+
+    // Stores [version], replacing any stored under the same ruleset and version
+    fn save_ruleset(&mut self, version: &RulesetVersion) -> Result<(), ErrorKind>;
+
+    // Removes the version of the rules [pin] refers to, if it is stored
+    fn remove_ruleset(&mut self, pin: &RulesetPin) -> Result<(), ErrorKind>;
+}
+
+// Keeps every Ticket in memory, lost whenever the process exits
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    count: u64,
+    tickets: BTreeMap<u64, Ticket>,
+    // Opaque IDs of the stored Tickets which have one
+    public_ids: BTreeMap<String, u64>,
+    events: Vec<Event>,
+    // Sequence number of the last Event, which may have been pruned since
+    seq: u64,
+    rulesets: BTreeMap<RulesetPin, RulesetVersion>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        MemoryStore::default()
+    }
+
+    // Internal function for finding the next unique ID
+    fn find_unused_key(&self) -> u64 {
+        (self.count..)
+            .filter(|k| !self.tickets.contains_key(k))
+            .take(1)
+            .sum()
+    }
+}
+
+impl TicketStore for MemoryStore {
+    fn allocate_id(&mut self) -> Result<u64, ErrorKind> {
+        self.count += 1;
+
+        if self.tickets.contains_key(&self.count) {
+            self.count = self.find_unused_key()
+        }
+
+        Ok(self.count)
+    }
+
+    fn insert(&mut self, id: u64, ticket: Ticket) -> Result<(), ErrorKind> {
+        self.remove(id)?;
+        if let Some(public_id) = &ticket.public_id {
+            self.public_ids.insert(public_id.clone(), id);
+        }
+        self.tickets.insert(id, ticket);
+        Ok(())
+    }
+
+    fn get(&self, id: u64) -> Result<Option<Ticket>, ErrorKind> {
+        Ok(self.tickets.get(&id).cloned())
+    }
+
+    fn find(&self, public_id: &str) -> Result<Option<u64>, ErrorKind> {
+        Ok(self.public_ids.get(public_id).cloned())
+    }
+
+    fn update(&mut self, id: u64, ticket: Ticket) -> Result<(), ErrorKind> {
+        match self.tickets.contains_key(&id) {
+            true => self.insert(id, ticket),
+            false => Err(ErrorKind::TicketNotFound(id.to_string())),
+        }
+    }
+
+    fn remove(&mut self, id: u64) -> Result<Option<Ticket>, ErrorKind> {
+        let ticket = self.tickets.remove(&id);
+        if let Some(public_id) = ticket.as_ref().and_then(|t| t.public_id.as_ref()) {
+            self.public_ids.remove(public_id);
+        }
+        Ok(ticket)
+    }
+
+    fn range(
+        &self,
+        after: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<(u64, Ticket)>, ErrorKind> {
+        let start = match after {
+            Some(id) => Bound::Excluded(id),
+            None => Bound::Unbounded,
+        };
+
+        Ok(self
+            .tickets
+            .range((start, Bound::Unbounded))
+            .take(limit.unwrap_or(usize::max_value()))
+            .map(|(id, ticket)| (*id, ticket.clone()))
+            .collect::<Vec<(u64, Ticket)>>())
+    }
+
+    fn len(&self) -> Result<u64, ErrorKind> {
+        Ok(self.tickets.len() as u64)
+    }
+
+    fn count_in(&self, states: &[State]) -> Result<u64, ErrorKind> {
+        Ok(self
+            .tickets
+            .values()
+            .filter(|ticket| states.contains(&ticket.state))
+            .count() as u64)
+    }
+
+    fn last_id(&self) -> Result<u64, ErrorKind> {
+        Ok(self.count)
+    }
+
+    fn set_last_id(&mut self, id: u64) -> Result<(), ErrorKind> {
+        self.count = id;
+        Ok(())
+    }
+
+    fn append_event(&mut self, event: &Event) -> Result<(), ErrorKind> {
+        self.seq = self.seq.max(event.seq);
+        self.events.push(event.clone());
+        Ok(())
+    }
+
+    fn commit(&mut self, event: &Event, ticket: Option<Ticket>) -> Result<(), ErrorKind> {
+        // Nothing past this point can fail, so there is no partial change to undo
+        self.append_event(event)?;
+        match ticket {
+            Some(ticket) => self.insert(event.ticket, ticket),
+            None => {
+                self.events.retain(|kept| kept.ticket != event.ticket);
+                self.remove(event.ticket).map(|_| ())
+            }
+        }
+    }
+
+    fn events(&self, id: Option<u64>) -> Result<Vec<Event>, ErrorKind> {
+        Ok(self
+            .events
+            .iter()
+            .filter(|event| id.map_or(true, |id| event.ticket == id))
+            .cloned()
+            .collect())
+    }
+
+    fn last_seq(&self) -> Result<u64, ErrorKind> {
+        Ok(self.seq)
+    }
+
+    fn set_last_seq(&mut self, seq: u64) -> Result<(), ErrorKind> {
+        self.seq = seq;
+        Ok(())
+    }
+
+    fn rulesets(&self) -> Result<Vec<RulesetVersion>, ErrorKind> {
+        Ok(self.rulesets.values().cloned().collect())
+    }
+
+    fn save_ruleset(&mut self, version: &RulesetVersion) -> Result<(), ErrorKind> {
+        self.rulesets.insert(version.pin(), version.clone());
+        Ok(())
+    }
+
+    fn remove_ruleset(&mut self, pin: &RulesetPin) -> Result<(), ErrorKind> {
+        self.rulesets.remove(pin);
+        Ok(())
+    }
+}
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+    use crate::models::EventKind;
+
+    #[test]
+    fn MemoryStore_allocate_id() {
+        let mut store = MemoryStore::new();
+        assert_eq!(store.allocate_id().unwrap(), 1);
+        store.insert(2, Ticket::from(1)).unwrap();
+
+        // Skips over ids that were inserted directly
+        assert_eq!(store.allocate_id().unwrap(), 3);
+        assert_eq!(store.last_id().unwrap(), 3);
+
+        store.set_last_id(10).unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 11);
+    }
+
+    #[test]
+    fn MemoryStore_insert_get_remove() {
+        let mut store = MemoryStore::new();
+        let ticket = Ticket::from(3);
+        store.insert(1, ticket.clone()).unwrap();
+
+        assert_eq!(store.get(1).unwrap(), Some(ticket.clone()));
+        assert_eq!(store.get(2).unwrap(), None);
+        assert_eq!(store.len().unwrap(), 1);
+        assert_eq!(store.remove(1).unwrap(), Some(ticket));
+        assert_eq!(store.len().unwrap(), 0);
+    }
+
+    #[test]
+    fn MemoryStore_find() {
+        let mut store = MemoryStore::new();
+        let mut ticket = Ticket::from(1);
+        ticket.public_id = Some(String::from("opaque"));
+        store.insert(7, ticket.clone()).unwrap();
+        store.insert(8, Ticket::from(1)).unwrap();
+
+        assert_eq!(store.find("opaque").unwrap(), Some(7));
+        assert_eq!(store.find("8").unwrap(), None);
+        store.update(7, ticket).unwrap();
+        assert_eq!(store.find("opaque").unwrap(), Some(7));
+        store.remove(7).unwrap();
+        assert_eq!(store.find("opaque").unwrap(), None);
+    }
+
+    #[test]
+    fn MemoryStore_update() {
+        let mut store = MemoryStore::new();
+        store.insert(1, Ticket::from(3)).unwrap();
+        let replacement = Ticket::from(5);
+
+        assert!(store.update(1, replacement.clone()).is_ok());
+        assert_eq!(store.get(1).unwrap(), Some(replacement));
+        assert!(store.update(2, Ticket::from(1)).is_err());
+    }
+
+    #[test]
+    fn MemoryStore_range() {
+        let mut store = MemoryStore::new();
+        (1..=5).for_each(|id| store.insert(id, Ticket::from(id)).unwrap());
+        let ids = |after, limit| {
+            store
+                .range(after, limit)
+                .unwrap()
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<u64>>()
+        };
+
+        assert_eq!(ids(None, None), vec![1, 2, 3, 4, 5]);
+        assert_eq!(ids(Some(2), None), vec![3, 4, 5]);
+        assert_eq!(ids(Some(2), Some(2)), vec![3, 4]);
+        assert_eq!(ids(Some(5), None), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn MemoryStore_count_in() {
+        let mut store = MemoryStore::new();
+        (1..=5).for_each(|id| store.insert(id, Ticket::from(id)).unwrap());
+        let mut voided = Ticket::from(1);
+        voided.state = State::Voided;
+        store.update(2, voided).unwrap();
+
+        assert_eq!(store.count_in(&[State::Open]).unwrap(), 4);
+        assert_eq!(store.count_in(&[State::Open, State::Voided]).unwrap(), 5);
+        assert_eq!(store.count_in(&[State::Checked]).unwrap(), 0);
+    }
+
+    #[test]
+    fn MemoryStore_events() {
+        let mut store = MemoryStore::new();
+        assert_eq!(store.last_seq().unwrap(), 0);
+        for (seq, ticket) in [(1, 1), (2, 2), (3, 1)].iter() {
+            store
+                .append_event(&Event {
+                    seq: *seq,
+                    ticket: *ticket,
+                    at: 0,
+                    kind: EventKind::TicketVoided,
+                })
+                .unwrap();
+        }
+
+        assert_eq!(store.last_seq().unwrap(), 3);
+        assert_eq!(store.events(None).unwrap().len(), 3);
+        let seqs: Vec<u64> = store
+            .events(Some(1))
+            .unwrap()
+            .iter()
+            .map(|e| e.seq)
+            .collect();
+        assert_eq!(seqs, vec![1, 3]);
+
+        // Removing a Ticket prunes its Events, but their sequence numbers stay used
+        store
+            .commit(
+                &Event {
+                    seq: 4,
+                    ticket: 1,
+                    at: 0,
+                    kind: EventKind::TicketRemoved,
+                },
+                None,
+            )
+            .unwrap();
+        assert_eq!(store.last_seq().unwrap(), 4);
+        assert!(store.events(Some(1)).unwrap().is_empty());
+        assert_eq!(store.events(None).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn MemoryStore_rulesets() {
+        let mut store = MemoryStore::new();
+        let version = |id: &str, version| RulesetVersion {
+            id: String::from(id),
+            version,
+            rules: Vec::new(),
+        };
+        for saved in &[
+            version("wide", 1),
+            version("classic", 2),
+            version("classic", 1),
+        ] {
+            store.save_ruleset(saved).unwrap();
+        }
+        store.remove_ruleset(&version("classic", 2).pin()).unwrap();
+
+        assert_eq!(
+            store.rulesets().unwrap(),
+            vec![version("classic", 1), version("wide", 1)]
+        );
+    }
+}
@@ -0,0 +1,941 @@
+use {
+    super::{
+        super::{Batch, Line, State, Transition},
+        ErrorKind, Event, RulesetPin, RulesetVersion, Ticket, TicketStore,
+    },
+    rusqlite::{params, Connection, OptionalExtension, Transaction, NO_PARAMS},
+    std::{fmt, path::Path, str::FromStr, sync::Mutex},
+};
+
+// Schema migrations, applied in order at boot. The database's user_version
+// counts how many have been applied, so only ever append to this list
+const MIGRATIONS: &[&str] = &[
+    // 1: Tickets, their Lines and State transitions
+    "CREATE TABLE tickets (
+        id INTEGER PRIMARY KEY,
+        state TEXT NOT NULL,
+        result TEXT
+    );
+    CREATE INDEX tickets_by_state ON tickets (state, id);
+    CREATE TABLE lines (
+        ticket_id INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        idx INTEGER NOT NULL,
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        z INTEGER NOT NULL,
+        PRIMARY KEY (ticket_id, idx)
+    ) WITHOUT ROWID;
+    CREATE TABLE transitions (
+        ticket_id INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        seq INTEGER NOT NULL,
+        from_state TEXT,
+        to_state TEXT NOT NULL,
+        at INTEGER NOT NULL,
+        PRIMARY KEY (ticket_id, seq)
+    ) WITHOUT ROWID;
+    CREATE TABLE counters (
+        name TEXT PRIMARY KEY,
+        value INTEGER NOT NULL
+    );
+    INSERT INTO counters (name, value) VALUES ('last_id', 0);",
+    // 2: Opaque Ticket IDs, Tickets created before them have none
+    "ALTER TABLE tickets ADD COLUMN public_id TEXT;
+    CREATE UNIQUE INDEX tickets_by_public_id ON tickets (public_id);",
+    // 3: Where each Ticket's Lines came from, Tickets created before this have none
+    "CREATE TABLE batches (
+        ticket_id INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        seq INTEGER NOT NULL,
+        at INTEGER NOT NULL,
+        start INTEGER NOT NULL,
+        end INTEGER NOT NULL,
+        requester TEXT,
+        PRIMARY KEY (ticket_id, seq)
+    ) WITHOUT ROWID;",
+    // 4: The Events every Ticket is derived from, pruned along with their Ticket, and
+    // the last one's sequence number, kept apart so pruning never hands it out again
+    "CREATE TABLE events (
+        seq INTEGER PRIMARY KEY,
+        ticket_id INTEGER NOT NULL,
+        at INTEGER NOT NULL,
+        event TEXT NOT NULL
+    );
+    CREATE INDEX events_by_ticket ON events (ticket_id, seq);
+    INSERT INTO counters (name, value) VALUES ('last_seq', 0);",
+    // 5: Lines of any width, their numbers kept as a json array
+    "CREATE TABLE lines_any_width (
+        ticket_id INTEGER NOT NULL REFERENCES tickets (id) ON DELETE CASCADE,
+        idx INTEGER NOT NULL,
+        numbers TEXT NOT NULL,
+        PRIMARY KEY (ticket_id, idx)
+    ) WITHOUT ROWID;
+    INSERT INTO lines_any_width (ticket_id, idx, numbers)
+        SELECT ticket_id, idx, '[' || x || ',' || y || ',' || z || ']' FROM lines;
+    DROP TABLE lines;
+    ALTER TABLE lines_any_width RENAME TO lines;",
+    // 6: The Game each Ticket belongs to, Tickets from before there were games are classic
+    "ALTER TABLE tickets ADD COLUMN game TEXT NOT NULL DEFAULT 'classic';",
+    // 7: Every version of the rules Tickets are scored by, kept as json, and the one
+    // each Ticket is pinned to. Tickets from before rules were versioned have none
+    "CREATE TABLE rulesets (
+        id TEXT NOT NULL,
+        version INTEGER NOT NULL,
+        rules TEXT NOT NULL,
+        PRIMARY KEY (id, version)
+    ) WITHOUT ROWID;
+    ALTER TABLE tickets ADD COLUMN rules_id TEXT;
+    ALTER TABLE tickets ADD COLUMN rules_version INTEGER;",
+];
+
+// Keeps every Ticket in an embedded SQLite database. Each call runs in its own
+// transaction, so a Ticket is never left half written
+pub struct SqliteStore {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    // Opens, or creates, the database at [path] and brings its schema up to date
+    pub fn open(path: &Path) -> Result<Self, ErrorKind> {
+        SqliteStore::with_connection(Connection::open(path).map_err(storage)?)
+    }
+
+    // A database that only lives as long as the store
+    #[cfg(test)]
+    pub fn in_memory() -> Result<Self, ErrorKind> {
+        SqliteStore::with_connection(Connection::open_in_memory().map_err(storage)?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self, ErrorKind> {
+        conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")
+            .map_err(storage)?;
+        migrate(&mut conn)?;
+
+        Ok(SqliteStore {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    // Runs [f] inside a transaction, committing only if it succeeds
+    fn transaction<T, F>(&self, f: F) -> Result<T, ErrorKind>
+    where
+        F: FnOnce(&Transaction) -> Result<T, ErrorKind>,
+    {
+        let mut conn = self.conn.lock().map_err(storage)?;
+        let tx = conn.transaction().map_err(storage)?;
+        let value = f(&tx)?;
+        tx.commit().map_err(storage)?;
+        Ok(value)
+    }
+}
+
+// Applies every migration the database hasn't seen yet, one transaction each
+fn migrate(conn: &mut Connection) -> Result<(), ErrorKind> {
+    let version: i64 = conn
+        .query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+        .map_err(storage)?;
+    if version as usize > MIGRATIONS.len() {
+        return Err(ErrorKind::Storage(format!(
+            "database schema version {} is newer than the {} this build knows",
+            version,
+            MIGRATIONS.len()
+        )));
+    }
+
+    for (applied, migration) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let tx = conn.transaction().map_err(storage)?;
+        tx.execute_batch(migration).map_err(storage)?;
+        tx.execute_batch(&format!("PRAGMA user_version = {}", applied + 1))
+            .map_err(storage)?;
+        tx.commit().map_err(storage)?;
+    }
+
+    Ok(())
+}
+
+impl TicketStore for SqliteStore {
+    fn allocate_id(&mut self) -> Result<u64, ErrorKind> {
+        self.transaction(|tx| {
+            let mut id = last_id(tx)? + 1;
+            // Skip over IDs that were inserted directly
+            while exists(tx, id)? {
+                id += 1
+            }
+
+            set_last_id(tx, id)?;
+            Ok(id)
+        })
+    }
+
+    fn insert(&mut self, id: u64, ticket: Ticket) -> Result<(), ErrorKind> {
+        self.transaction(|tx| insert(tx, id, &ticket))
+    }
+
+    fn get(&self, id: u64) -> Result<Option<Ticket>, ErrorKind> {
+        self.transaction(|tx| read(tx, id))
+    }
+
+    fn find(&self, public_id: &str) -> Result<Option<u64>, ErrorKind> {
+        self.transaction(|tx| {
+            tx.query_row(
+                "SELECT id FROM tickets WHERE public_id = ?1",
+                params![public_id],
+                |row| row.get::<_, i64>(0),
+            )
+            .optional()
+            .map(|id| id.map(|id| id as u64))
+            .map_err(storage)
+        })
+    }
+
+    fn update(&mut self, id: u64, ticket: Ticket) -> Result<(), ErrorKind> {
+        self.transaction(|tx| update(tx, id, &ticket))
+    }
+
+    fn remove(&mut self, id: u64) -> Result<Option<Ticket>, ErrorKind> {
+        self.transaction(|tx| {
+            let ticket = read(tx, id)?;
+            tx.execute("DELETE FROM tickets WHERE id = ?1", params![id as i64])
+                .map_err(storage)?;
+            Ok(ticket)
+        })
+    }
+
+    fn range(
+        &self,
+        after: Option<u64>,
+        limit: Option<usize>,
+    ) -> Result<Vec<(u64, Ticket)>, ErrorKind> {
+        self.transaction(|tx| {
+            let mut statement = tx
+                .prepare("SELECT id FROM tickets WHERE id > ?1 ORDER BY id LIMIT ?2")
+                .map_err(storage)?;
+            let ids = statement
+                .query_map(
+                    params![
+                        after.map_or(-1, |id| id as i64),
+                        limit.map_or(-1, |limit| limit as i64)
+                    ],
+                    |row| row.get::<_, i64>(0),
+                )
+                .map_err(storage)?
+                .collect::<Result<Vec<i64>, _>>()
+                .map_err(storage)?;
+
+            ids.into_iter()
+                .filter_map(|id| match read(tx, id as u64) {
+                    Ok(Some(ticket)) => Some(Ok((id as u64, ticket))),
+                    Ok(None) => None,
+                    Err(e) => Some(Err(e)),
+                })
+                .collect()
+        })
+    }
+
+    fn len(&self) -> Result<u64, ErrorKind> {
+        self.transaction(|tx| {
+            tx.query_row("SELECT COUNT(*) FROM tickets", NO_PARAMS, |row| {
+                row.get::<_, i64>(0)
+            })
+            .map(|n| n as u64)
+            .map_err(storage)
+        })
+    }
+
+    fn count_in(&self, states: &[State]) -> Result<u64, ErrorKind> {
+        self.transaction(|tx| {
+            let mut statement = tx
+                .prepare("SELECT state, COUNT(*) FROM tickets GROUP BY state")
+                .map_err(storage)?;
+            let counts = statement
+                .query_map(NO_PARAMS, |row| {
+                    Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+                })
+                .map_err(storage)?
+                .collect::<Result<Vec<(String, i64)>, _>>()
+                .map_err(storage)?;
+
+            Ok(counts
+                .into_iter()
+                .filter(|(state, _)| states.iter().any(|s| s.to_string() == *state))
+                .map(|(_, count)| count as u64)
+                .sum())
+        })
+    }
+
+    fn last_id(&self) -> Result<u64, ErrorKind> {
+        self.transaction(last_id)
+    }
+
+    fn set_last_id(&mut self, id: u64) -> Result<(), ErrorKind> {
+        self.transaction(|tx| set_last_id(tx, id))
+    }
+
+    fn append_event(&mut self, event: &Event) -> Result<(), ErrorKind> {
+        self.transaction(|tx| append_event(tx, event))
+    }
+
+    fn commit(&mut self, event: &Event, ticket: Option<Ticket>) -> Result<(), ErrorKind> {
+        self.transaction(|tx| {
+            append_event(tx, event)?;
+            match (ticket, exists(tx, event.ticket)?) {
+                (Some(ticket), true) => update(tx, event.ticket, &ticket),
+                (Some(ticket), false) => insert(tx, event.ticket, &ticket),
+                (None, _) => {
+                    for statement in &[
+                        "DELETE FROM tickets WHERE id = ?1",
+                        "DELETE FROM events WHERE ticket_id = ?1",
+                    ] {
+                        tx.execute(statement, params![event.ticket as i64])
+                            .map_err(storage)?;
+                    }
+                    Ok(())
+                }
+            }
+        })
+    }
+
+    fn events(&self, id: Option<u64>) -> Result<Vec<Event>, ErrorKind> {
+        self.transaction(|tx| {
+            let mut statement = tx
+                .prepare(
+                    "SELECT seq, ticket_id, at, event FROM events
+                     WHERE ?1 IS NULL OR ticket_id = ?1 ORDER BY seq",
+                )
+                .map_err(storage)?;
+            let rows = statement
+                .query_map(params![id.map(|id| id as i64)], |row| {
+                    Ok((
+                        row.get::<_, i64>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                })
+                .map_err(storage)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(storage)?;
+
+            rows.into_iter()
+                .map(|(seq, ticket, at, kind)| {
+                    Ok(Event {
+                        seq: seq as u64,
+                        ticket: ticket as u64,
+                        at: at as u64,
+                        kind: serde_json::from_str(&kind).map_err(storage)?,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn last_seq(&self) -> Result<u64, ErrorKind> {
+        self.transaction(|tx| {
+            tx.query_row(
+                "SELECT value FROM counters WHERE name = 'last_seq'",
+                NO_PARAMS,
+                |row| row.get::<_, i64>(0),
+            )
+            .map(|n| n as u64)
+            .map_err(storage)
+        })
+    }
+
+    fn set_last_seq(&mut self, seq: u64) -> Result<(), ErrorKind> {
+        self.transaction(|tx| {
+            tx.execute(
+                "UPDATE counters SET value = ?1 WHERE name = 'last_seq'",
+                params![seq as i64],
+            )
+            .map(|_| ())
+            .map_err(storage)
+        })
+    }
+
+    fn rulesets(&self) -> Result<Vec<RulesetVersion>, ErrorKind> {
+        self.transaction(|tx| {
+            let mut statement = tx
+                .prepare("SELECT id, version, rules FROM rulesets ORDER BY id, version")
+                .map_err(storage)?;
+            let rows = statement
+                .query_map(NO_PARAMS, |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, i64>(1)?,
+                        row.get::<_, String>(2)?,
+                    ))
+                })
+                .map_err(storage)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(storage)?;
+
+            rows.into_iter()
+                .map(|(id, version, rules)| {
+                    Ok(RulesetVersion {
+                        id,
+                        version: version as u32,
+                        rules: serde_json::from_str(&rules).map_err(storage)?,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn save_ruleset(&mut self, version: &RulesetVersion) -> Result<(), ErrorKind> {
+        let rules = serde_json::to_string(&version.rules).map_err(storage)?;
+        self.transaction(|tx| {
+            tx.execute(
+                "INSERT OR REPLACE INTO rulesets (id, version, rules) VALUES (?1, ?2, ?3)",
+                params![version.id, version.version as i64, rules],
+            )
+            .map(|_| ())
+            .map_err(storage)
+        })
+    }
+
+    fn remove_ruleset(&mut self, pin: &RulesetPin) -> Result<(), ErrorKind> {
+        self.transaction(|tx| {
+            tx.execute(
+                "DELETE FROM rulesets WHERE id = ?1 AND version = ?2",
+                params![pin.id, pin.version as i64],
+            )
+            .map(|_| ())
+            .map_err(storage)
+        })
+    }
+}
+
+// Stores [ticket] under [id], replacing whatever was stored there
+fn insert(tx: &Transaction, id: u64, ticket: &Ticket) -> Result<(), ErrorKind> {
+    tx.execute("DELETE FROM tickets WHERE id = ?1", params![id as i64])
+        .map_err(storage)?;
+    tx.execute(
+        "INSERT INTO tickets (id, state, result, public_id, game, rules_id, rules_version)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id as i64,
+            format!("{}", ticket.state),
+            result(ticket)?,
+            ticket.public_id,
+            ticket.game,
+            ticket.rules.as_ref().map(|pin| pin.id.as_str()),
+            ticket.rules.as_ref().map(|pin| pin.version as i64)
+        ],
+    )
+    .map_err(storage)?;
+    write_children(tx, id, ticket)
+}
+
+// Replaces the Ticket stored under [id], or returns an error if there is none
+fn update(tx: &Transaction, id: u64, ticket: &Ticket) -> Result<(), ErrorKind> {
+    if !exists(tx, id)? {
+        return Err(ErrorKind::TicketNotFound(id.to_string()));
+    }
+    insert(tx, id, ticket)
+}
+
+fn append_event(tx: &Transaction, event: &Event) -> Result<(), ErrorKind> {
+    let kind = serde_json::to_string(&event.kind).map_err(storage)?;
+    tx.execute(
+        "INSERT INTO events (seq, ticket_id, at, event) VALUES (?1, ?2, ?3, ?4)",
+        params![event.seq as i64, event.ticket as i64, event.at as i64, kind],
+    )
+    .map_err(storage)?;
+    tx.execute(
+        "UPDATE counters SET value = MAX(value, ?1) WHERE name = 'last_seq'",
+        params![event.seq as i64],
+    )
+    .map(|_| ())
+    .map_err(storage)
+}
+
+// Loads the Ticket stored under [id] along with its Lines, transitions and batches
+fn read(tx: &Transaction, id: u64) -> Result<Option<Ticket>, ErrorKind> {
+    let row = tx
+        .query_row(
+            "SELECT state, result, public_id, game, rules_id, rules_version
+             FROM tickets WHERE id = ?1",
+            params![id as i64],
+            |row| {
+                let pin = match (
+                    row.get::<_, Option<String>>(4)?,
+                    row.get::<_, Option<i64>>(5)?,
+                ) {
+                    (Some(id), Some(version)) => Some(RulesetPin {
+                        id,
+                        version: version as u32,
+                    }),
+                    _ => None,
+                };
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, String>(3)?,
+                    pin,
+                ))
+            },
+        )
+        .optional()
+        .map_err(storage)?;
+    let (state, result, public_id, game, rules) = match row {
+        Some(row) => row,
+        None => return Ok(None),
+    };
+
+    let mut statement = tx
+        .prepare("SELECT numbers FROM lines WHERE ticket_id = ?1 ORDER BY idx")
+        .map_err(storage)?;
+    let line_list = statement
+        .query_map(params![id as i64], |row| row.get::<_, String>(0))
+        .map_err(storage)?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(storage)?
+        .iter()
+        .map(|numbers| serde_json::from_str(numbers).map(Line).map_err(storage))
+        .collect::<Result<Vec<Line>, ErrorKind>>()?;
+
+    let mut statement = tx
+        .prepare(
+            "SELECT from_state, to_state, at FROM transitions WHERE ticket_id = ?1 ORDER BY seq",
+        )
+        .map_err(storage)?;
+    let transitions = statement
+        .query_map(params![id as i64], |row| {
+            Ok((
+                row.get::<_, Option<String>>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+            ))
+        })
+        .map_err(storage)?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(storage)?
+        .into_iter()
+        .map(|(from, to, at)| {
+            Ok(Transition {
+                from: match from {
+                    Some(from) => Some(state_of(&from)?),
+                    None => None,
+                },
+                to: state_of(&to)?,
+                at: at as u64,
+            })
+        })
+        .collect::<Result<Vec<Transition>, ErrorKind>>()?;
+
+    let mut statement = tx
+        .prepare("SELECT at, start, end, requester FROM batches WHERE ticket_id = ?1 ORDER BY seq")
+        .map_err(storage)?;
+    let batches = statement
+        .query_map(params![id as i64], |row| {
+            Ok(Batch {
+                at: row.get::<_, i64>(0)? as u64,
+                start: row.get::<_, i64>(1)? as u64,
+                end: row.get::<_, i64>(2)? as u64,
+                requester: row.get(3)?,
+            })
+        })
+        .map_err(storage)?
+        .collect::<Result<Vec<Batch>, _>>()
+        .map_err(storage)?;
+
+    Ok(Some(Ticket {
+        public_id,
+        game,
+        rules,
+        line_list,
+        state: state_of(&state)?,
+        transitions,
+        result: match result {
+            Some(result) => Some(serde_json::from_str(&result).map_err(storage)?),
+            None => None,
+        },
+        batches,
+    }))
+}
+
+// Writes a Ticket's Lines, transitions and batches
+fn write_children(tx: &Transaction, id: u64, ticket: &Ticket) -> Result<(), ErrorKind> {
+    let mut statement = tx
+        .prepare_cached("INSERT INTO lines (ticket_id, idx, numbers) VALUES (?1, ?2, ?3)")
+        .map_err(storage)?;
+    for (idx, Line(numbers)) in ticket.line_list.iter().enumerate() {
+        statement
+            .execute(params![
+                id as i64,
+                idx as i64,
+                serde_json::to_string(numbers).map_err(storage)?
+            ])
+            .map_err(storage)?;
+    }
+
+    let mut statement = tx
+        .prepare_cached(
+            "INSERT INTO transitions (ticket_id, seq, from_state, to_state, at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )
+        .map_err(storage)?;
+    for (seq, transition) in ticket.transitions.iter().enumerate() {
+        statement
+            .execute(params![
+                id as i64,
+                seq as i64,
+                transition.from.map(|from| format!("{}", from)),
+                format!("{}", transition.to),
+                transition.at as i64
+            ])
+            .map_err(storage)?;
+    }
+
+    let mut statement = tx
+        .prepare_cached(
+            "INSERT INTO batches (ticket_id, seq, at, start, end, requester)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )
+        .map_err(storage)?;
+    for (seq, batch) in ticket.batches.iter().enumerate() {
+        statement
+            .execute(params![
+                id as i64,
+                seq as i64,
+                batch.at as i64,
+                batch.start as i64,
+                batch.end as i64,
+                batch.requester
+            ])
+            .map_err(storage)?;
+    }
+
+    Ok(())
+}
+
+// The Ticket's stored status check response, as JSON
+fn result(ticket: &Ticket) -> Result<Option<String>, ErrorKind> {
+    match &ticket.result {
+        Some(result) => Ok(Some(serde_json::to_string(result).map_err(storage)?)),
+        None => Ok(None),
+    }
+}
+
+fn exists(tx: &Transaction, id: u64) -> Result<bool, ErrorKind> {
+    tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM tickets WHERE id = ?1)",
+        params![id as i64],
+        |row| row.get(0),
+    )
+    .map_err(storage)
+}
+
+fn last_id(tx: &Transaction) -> Result<u64, ErrorKind> {
+    tx.query_row(
+        "SELECT value FROM counters WHERE name = 'last_id'",
+        NO_PARAMS,
+        |row| row.get::<_, i64>(0),
+    )
+    .map(|n| n as u64)
+    .map_err(storage)
+}
+
+fn set_last_id(tx: &Transaction, id: u64) -> Result<(), ErrorKind> {
+    tx.execute(
+        "UPDATE counters SET value = ?1 WHERE name = 'last_id'",
+        params![id as i64],
+    )
+    .map(|_| ())
+    .map_err(storage)
+}
+
+fn state_of(s: &str) -> Result<State, ErrorKind> {
+    State::from_str(s).map_err(|_| ErrorKind::Storage(format!("unknown state '{}' stored", s)))
+}
+
+fn storage<E: fmt::Display>(e: E) -> ErrorKind {
+    ErrorKind::Storage(format!("{}", e))
+}
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+    use crate::models::EventKind;
+
+    // A Ticket that has been through a few transitions and was checked
+    fn checked_ticket() -> Ticket {
+        let mut raffle = crate::models::Raffle::with_store(
+            Box::new(SqliteStore::in_memory().unwrap()),
+            Default::default(),
+        );
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.append_ticket("1", 1, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+        let snapshot = raffle.snapshot().unwrap();
+        snapshot.tickets.into_iter().next().unwrap().1
+    }
+
+    // How many rows of [table] belong to the Ticket [id]
+    fn count(tx: &Transaction, table: &str, id: u64) -> Result<u64, ErrorKind> {
+        tx.query_row(
+            &format!("SELECT COUNT(*) FROM {} WHERE ticket_id = ?1", table),
+            params![id as i64],
+            |row| row.get::<_, i64>(0),
+        )
+        .map(|n| n as u64)
+        .map_err(storage)
+    }
+
+    #[test]
+    fn SqliteStore_migrate() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        migrate(&mut conn).unwrap();
+        migrate(&mut conn).unwrap();
+        let version: i64 = conn
+            .query_row("PRAGMA user_version", NO_PARAMS, |row| row.get(0))
+            .unwrap();
+        assert_eq!(version as usize, MIGRATIONS.len());
+
+        conn.execute_batch("PRAGMA user_version = 999").unwrap();
+        assert!(migrate(&mut conn).is_err());
+    }
+
+    #[test]
+    fn SqliteStore_migrate_lines() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        MIGRATIONS[..4]
+            .iter()
+            .for_each(|migration| conn.execute_batch(migration).unwrap());
+        conn.execute_batch(
+            "PRAGMA user_version = 4;
+            INSERT INTO tickets (id, state) VALUES (1, 'open');
+            INSERT INTO lines (ticket_id, idx, x, y, z) VALUES (1, 0, 2, 0, 1);",
+        )
+        .unwrap();
+        migrate(&mut conn).unwrap();
+
+        // Lines written as three columns read back as they were
+        let store = SqliteStore {
+            conn: Mutex::new(conn),
+        };
+        let ticket = store.get(1).unwrap().unwrap();
+        assert_eq!(ticket.line_list, vec![Line::from((2, 0, 1))]);
+        assert_eq!(ticket.game, crate::models::CLASSIC_GAME);
+    }
+
+    #[test]
+    fn SqliteStore_wide_lines() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let shape = crate::models::LineShape::new(5, 5).unwrap();
+        let mut ticket = Ticket::with_lines(shape.draw(4));
+        ticket.game = String::from("wide");
+        store.insert(1, ticket.clone()).unwrap();
+
+        assert_eq!(store.get(1).unwrap(), Some(ticket));
+    }
+
+    #[test]
+    fn SqliteStore_rulesets() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let classic = crate::models::Games::default();
+        let mut kept = classic.versions();
+        kept.push(RulesetVersion {
+            id: String::from("wide"),
+            version: 3,
+            rules: Vec::new(),
+        });
+        for version in &kept {
+            store.save_ruleset(version).unwrap();
+        }
+        assert_eq!(store.rulesets().unwrap(), kept);
+
+        store.remove_ruleset(&kept[1].pin()).unwrap();
+        assert_eq!(store.rulesets().unwrap(), kept[..1].to_vec());
+    }
+
+    #[test]
+    fn SqliteStore_pinned_ticket() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let mut ticket = checked_ticket();
+        assert!(ticket.rules.is_some());
+        store.insert(1, ticket.clone()).unwrap();
+        assert_eq!(store.get(1).unwrap(), Some(ticket.clone()));
+
+        ticket.rules = None;
+        store.update(1, ticket.clone()).unwrap();
+        assert_eq!(store.get(1).unwrap(), Some(ticket));
+    }
+
+    #[test]
+    fn SqliteStore_allocate_id() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 1);
+        store.insert(2, Ticket::from(1)).unwrap();
+
+        // Skips over ids that were inserted directly
+        assert_eq!(store.allocate_id().unwrap(), 3);
+        assert_eq!(store.last_id().unwrap(), 3);
+
+        store.set_last_id(10).unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 11);
+    }
+
+    #[test]
+    fn SqliteStore_insert_get_remove() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let ticket = checked_ticket();
+        store.insert(1, ticket.clone()).unwrap();
+
+        assert_eq!(store.get(1).unwrap(), Some(ticket.clone()));
+        assert_eq!(store.get(2).unwrap(), None);
+        assert_eq!(store.len().unwrap(), 1);
+        assert_eq!(store.remove(1).unwrap(), Some(ticket));
+        assert_eq!(store.len().unwrap(), 0);
+        assert_eq!(
+            count(
+                &store.conn.lock().unwrap().transaction().unwrap(),
+                "lines",
+                1
+            )
+            .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn SqliteStore_find() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let mut ticket = Ticket::from(1);
+        ticket.public_id = Some(String::from("opaque"));
+        store.insert(7, ticket.clone()).unwrap();
+        store.insert(8, Ticket::from(1)).unwrap();
+
+        assert_eq!(store.find("opaque").unwrap(), Some(7));
+        assert_eq!(store.find("8").unwrap(), None);
+        assert_eq!(store.get(7).unwrap(), Some(ticket));
+        store.remove(7).unwrap();
+        assert_eq!(store.find("opaque").unwrap(), None);
+    }
+
+    #[test]
+    fn SqliteStore_update() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let mut ticket = Ticket::from(3);
+        store.insert(1, ticket.clone()).unwrap();
+        ticket.transition("1", State::Amended, 0).unwrap();
+        ticket.append(Ticket::from(2).line_list, None, 0);
+
+        assert!(store.update(1, ticket.clone()).is_ok());
+        assert_eq!(store.get(1).unwrap(), Some(ticket));
+
+        let shorter = Ticket::from(1);
+        assert!(store.update(1, shorter.clone()).is_ok());
+        assert_eq!(store.get(1).unwrap(), Some(shorter));
+        assert!(store.update(2, Ticket::from(1)).is_err());
+    }
+
+    #[test]
+    fn SqliteStore_range() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        (1..=5).for_each(|id| store.insert(id, Ticket::from(id)).unwrap());
+        let ids = |after, limit| {
+            store
+                .range(after, limit)
+                .unwrap()
+                .iter()
+                .map(|(id, _)| *id)
+                .collect::<Vec<u64>>()
+        };
+
+        assert_eq!(ids(None, None), vec![1, 2, 3, 4, 5]);
+        assert_eq!(ids(Some(2), None), vec![3, 4, 5]);
+        assert_eq!(ids(Some(2), Some(2)), vec![3, 4]);
+        assert_eq!(ids(Some(5), None), Vec::<u64>::new());
+    }
+
+    #[test]
+    fn SqliteStore_count_in() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        (1..=5).for_each(|id| store.insert(id, Ticket::from(id)).unwrap());
+        let mut voided = Ticket::from(1);
+        voided.state = State::Voided;
+        store.update(2, voided).unwrap();
+
+        assert_eq!(store.count_in(&[State::Open]).unwrap(), 4);
+        assert_eq!(store.count_in(&[State::Open, State::Voided]).unwrap(), 5);
+        assert_eq!(store.count_in(&[State::Checked]).unwrap(), 0);
+    }
+
+    #[test]
+    fn SqliteStore_commit() {
+        let mut store = SqliteStore::in_memory().unwrap();
+        let (first, second) = (Ticket::from(2), Ticket::from(3));
+        let event = |seq, kind| Event {
+            seq,
+            ticket: 1,
+            at: 0,
+            kind,
+        };
+        store
+            .commit(&event(1, EventKind::TicketVoided), Some(first.clone()))
+            .unwrap();
+        assert_eq!(store.get(1).unwrap(), Some(first.clone()));
+
+        // A clashing Event leaves the Ticket as it was
+        let result = store.commit(&event(1, EventKind::TicketVoided), Some(second));
+        assert!(result.is_err());
+        assert_eq!(store.get(1).unwrap(), Some(first));
+        assert_eq!(store.last_seq().unwrap(), 1);
+
+        store
+            .commit(&event(2, EventKind::TicketRemoved), None)
+            .unwrap();
+        assert_eq!(store.get(1).unwrap(), None);
+        // Removing the Ticket prunes its Events, but their sequence numbers stay used
+        assert!(store.events(Some(1)).unwrap().is_empty());
+        assert_eq!(store.last_seq().unwrap(), 2);
+        store.set_last_seq(5).unwrap();
+        assert_eq!(store.last_seq().unwrap(), 5);
+    }
+
+    #[test]
+    fn SqliteStore_events() {
+        let mut raffle = crate::models::Raffle::with_store(
+            Box::new(SqliteStore::in_memory().unwrap()),
+            Default::default(),
+        );
+        raffle.new_ticket(Some(2), None, Some("alice")).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.append_ticket("1", 1, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+        let snapshot = raffle.snapshot().unwrap();
+        let seqs: Vec<u64> = snapshot.events.iter().map(|e| e.seq).collect();
+        assert_eq!(seqs, vec![1, 2, 3, 4]);
+        assert_eq!(raffle.get_history("1").unwrap().events.len(), 3);
+
+        // What was stored is enough to derive the same Tickets again
+        raffle.rebuild().unwrap();
+        assert_eq!(raffle.snapshot().unwrap(), snapshot);
+    }
+
+    #[test]
+    fn SqliteStore_reopen() {
+        let path = std::env::temp_dir().join(format!("raffle-sqlite-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let ticket = checked_ticket();
+        {
+            let mut store = SqliteStore::open(&path).unwrap();
+            let id = store.allocate_id().unwrap();
+            store.insert(id, ticket.clone()).unwrap();
+        }
+
+        let store = SqliteStore::open(&path).unwrap();
+        assert_eq!(store.get(1).unwrap(), Some(ticket));
+        assert_eq!(store.last_id().unwrap(), 1);
+        for suffix in &["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
+    }
+}
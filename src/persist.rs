@@ -0,0 +1,619 @@
+use {
+    crate::{
+        models::{ErrorKind, Event, Journal, Raffle, RulesetVersion, Snapshot, SNAPSHOT_VERSION},
+        shared::SharedRaffle,
+    },
+    serde::{Deserialize, Serialize},
+    std::{
+        ffi::OsString,
+        fmt,
+        fs::{self, File, OpenOptions},
+        io::{self, Write},
+        path::{Path, PathBuf},
+        process,
+        sync::Arc,
+        thread,
+        time::Duration,
+    },
+};
+
+// Where and how often the Raffle is written to disk
+#[derive(Debug, Clone, PartialEq)]
+pub struct SnapshotConfig {
+    pub path: PathBuf,
+    pub interval: Duration,
+    // Where Events recorded between snapshots are journaled
+    pub journal: PathBuf,
+    // Start from an empty Raffle instead of refusing to, when the snapshot or journal can't be read
+    pub ignore_corrupt: bool,
+}
+
+// Reads the snapshot at [path], if one has been written
+pub fn load(path: &Path) -> Result<Option<Snapshot>, PersistError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(PersistError::Io(e)),
+    };
+
+    let snapshot: Snapshot =
+        serde_json::from_str(&contents).map_err(|e| PersistError::Corrupt(format!("{}", e)))?;
+    match snapshot.version {
+        SNAPSHOT_VERSION => Ok(Some(snapshot)),
+        other => Err(PersistError::Corrupt(format!(
+            "unsupported snapshot version {}, expected {}",
+            other, SNAPSHOT_VERSION
+        ))),
+    }
+}
+
+// Writes [snapshot] to [path], via a temporary file which is renamed over the
+// old snapshot so a crash mid-write never leaves a half written file behind.
+// The directory is synced after the rename, so the new snapshot is durable
+// before the journal it covers is truncated
+pub fn save(path: &Path, snapshot: &Snapshot) -> Result<(), PersistError> {
+    let json = serde_json::to_vec(snapshot).map_err(|e| PersistError::Corrupt(format!("{}", e)))?;
+    let temporary = temporary_path(path);
+
+    let mut file = File::create(&temporary).map_err(PersistError::Io)?;
+    file.write_all(&json).map_err(PersistError::Io)?;
+    file.sync_all().map_err(PersistError::Io)?;
+    fs::rename(&temporary, path).map_err(PersistError::Io)?;
+
+    let directory = match path.parent() {
+        Some(parent) if parent != Path::new("") => parent,
+        _ => Path::new("."),
+    };
+    File::open(directory)
+        .and_then(|directory| directory.sync_all())
+        .map_err(PersistError::Io)
+}
+
+// Rebuilds [raffle] from the snapshot and journal named by [config], then
+// journals every following Event. Tickets from snapshots taken before Events were
+// recorded are adopted, so they get a history too
+pub fn restore(raffle: &mut Raffle, config: &SnapshotConfig) -> Result<(), PersistError> {
+    if let Some(snapshot) = load(&config.path)? {
+        raffle.restore(snapshot).map_err(PersistError::Raffle)?;
+    }
+
+    let (journal, records) = FileJournal::open(&config.journal)?;
+    for record in records {
+        match record {
+            Record::Event(event) => raffle.replay(*event),
+            Record::Ruleset { ruleset } => raffle.replay_ruleset(ruleset),
+        }
+        .map_err(PersistError::Raffle)?;
+    }
+    raffle
+        .set_journal(Box::new(journal))
+        .map_err(PersistError::Raffle)?;
+    raffle
+        .adopt_untracked()
+        .map(|_| ())
+        .map_err(PersistError::Raffle)
+}
+
+// Moves an unreadable snapshot and journal out of the way, so a new pair can
+// be started without losing what was in them
+pub fn quarantine(config: &SnapshotConfig) -> Result<(), PersistError> {
+    for path in &[&config.path, &config.journal] {
+        let mut aside = OsString::from(path.as_os_str());
+        aside.push(".corrupt");
+        if let Err(e) = fs::rename(path, aside) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(PersistError::Io(e));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+// Snapshots the Raffle behind [raffle] to [path], folding its journal into the
+// snapshot. Holds the write lock throughout so no mutation slips in between
+pub fn compact(raffle: &SharedRaffle, path: &Path) -> Result<(), PersistError> {
+    let mut raffle = raffle.write().map_err(PersistError::Raffle)?;
+    let snapshot = raffle.snapshot().map_err(PersistError::Raffle)?;
+
+    save(path, &snapshot)?;
+    raffle.truncate_journal().map_err(PersistError::Raffle)
+}
+
+// Snapshots the Raffle every [config.interval] on a background thread
+pub fn spawn_writer(raffle: Arc<SharedRaffle>, config: SnapshotConfig) {
+    thread::spawn(move || loop {
+        thread::sleep(config.interval);
+        if let Err(e) = compact(&raffle, &config.path) {
+            eprintln!(
+                "Failed to snapshot raffle to {}: {}",
+                config.path.display(),
+                e
+            )
+        }
+    });
+}
+
+// Snapshots the Raffle one last time when the process is asked to stop
+pub fn snapshot_on_shutdown(raffle: Arc<SharedRaffle>, config: SnapshotConfig) {
+    let handler = ctrlc::set_handler(move || {
+        let code = match compact(&raffle, &config.path) {
+            Ok(_) => 0,
+            Err(e) => {
+                eprintln!(
+                    "Failed to snapshot raffle to {}: {}",
+                    config.path.display(),
+                    e
+                );
+                1
+            }
+        };
+        process::exit(code)
+    });
+
+    if let Err(e) = handler {
+        eprintln!("Unable to snapshot the raffle on shutdown: {}", e)
+    }
+}
+
+// Appends each Event, and each new version of the rules, to a file as a line
+// of JSON, prefixed by its CRC32 checksum, and syncs it to disk before the Event
+// is acknowledged
+#[derive(Debug)]
+pub struct FileJournal {
+    file: File,
+    // Length of the file, and what it was before the last record was appended
+    length: u64,
+    previous: u64,
+    // Whether a failed append may have left part of a record past [length]
+    torn: bool,
+}
+
+impl FileJournal {
+    // Opens the journal at [path], creating it if needed, and returns every record
+    // already in it. A torn trailing record left by a crash mid-write is dropped,
+    // and cut off the file so new records don't land after it
+    pub fn open(path: &Path) -> Result<(FileJournal, Vec<Record>), PersistError> {
+        let contents = match fs::read(path) {
+            Ok(contents) => contents,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(PersistError::Io(e)),
+        };
+        let (records, valid) = read_records(&contents)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(PersistError::Io)?;
+        if valid < contents.len() {
+            eprintln!(
+                "Dropping {} bytes of incomplete journal record from {}",
+                contents.len() - valid,
+                path.display()
+            );
+            file.set_len(valid as u64).map_err(PersistError::Io)?;
+            file.sync_all().map_err(PersistError::Io)?;
+        }
+
+        let length = valid as u64;
+        Ok((
+            FileJournal {
+                file,
+                length,
+                previous: length,
+                torn: false,
+            },
+            records,
+        ))
+    }
+
+    // Internal function for appending [record] and syncing it to disk. A failed
+    // write is cut back off the file, so the next record doesn't land after it
+    fn append(&mut self, record: &Record) -> Result<(), ErrorKind> {
+        let json = serde_json::to_string(record).map_err(storage)?;
+        let line = format!("{:08x} {}\n", crc32fast::hash(json.as_bytes()), json);
+
+        if self.torn {
+            self.file.set_len(self.length).map_err(storage)?;
+            self.torn = false;
+        }
+        let written = self.file.write_all(line.as_bytes());
+        if let Err(e) = written.and_then(|_| self.file.sync_data()) {
+            self.torn = self.file.set_len(self.length).is_err();
+            return Err(storage(e));
+        }
+
+        self.previous = self.length;
+        self.length += line.len() as u64;
+        Ok(())
+    }
+}
+
+impl Journal for FileJournal {
+    fn record(&mut self, event: &Event) -> Result<(), ErrorKind> {
+        self.append(&Record::Event(Box::new(event.clone())))
+    }
+
+    fn record_ruleset(&mut self, version: &RulesetVersion) -> Result<(), ErrorKind> {
+        self.append(&Record::Ruleset {
+            ruleset: version.clone(),
+        })
+    }
+
+    fn rollback(&mut self) -> Result<(), ErrorKind> {
+        self.file.set_len(self.previous).map_err(storage)?;
+        self.length = self.previous;
+        self.torn = false;
+        self.file.sync_all().map_err(storage)
+    }
+
+    fn truncate(&mut self) -> Result<(), ErrorKind> {
+        self.file.set_len(0).map_err(storage)?;
+        self.length = 0;
+        self.previous = 0;
+        self.torn = false;
+        self.file.sync_all().map_err(storage)
+    }
+}
+
+// What a journal holds: an Event, or a version of the rules written as {"ruleset": ..}
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Record {
+    Event(Box<Event>),
+    Ruleset { ruleset: RulesetVersion },
+}
+
+// Parses every journal record in [contents], returning them along with how many
+// bytes they span. Only the last record may be damaged, anything before it
+// was acknowledged and must be intact
+fn read_records(contents: &[u8]) -> Result<(Vec<Record>, usize), PersistError> {
+    let mut records = Vec::new();
+    let mut valid = 0;
+
+    // Every record ends in a newline, so whatever follows the last one is a torn write
+    let mut lines = contents.split(|b| *b == b'\n').peekable();
+    let mut number = 0;
+    while let Some(line) = lines.next() {
+        number += 1;
+        let last = lines.peek().map_or(true, |next| next.is_empty());
+        match parse_record(line) {
+            Some(record) if valid + line.len() < contents.len() => {
+                records.push(record);
+                valid += line.len() + 1;
+            }
+            _ if last => break,
+            _ => {
+                return Err(PersistError::Corrupt(format!(
+                    "journal record {} is damaged",
+                    number
+                )))
+            }
+        }
+    }
+
+    Ok((records, valid))
+}
+
+// A record is "<crc32 as 8 hex digits> <json>\n"
+fn parse_record(line: &[u8]) -> Option<Record> {
+    let line = std::str::from_utf8(line).ok()?;
+    if line.len() < 9 || !line.is_char_boundary(8) || &line[8..9] != " " {
+        return None;
+    }
+    let (checksum, json) = (&line[..8], &line[9..]);
+
+    match u32::from_str_radix(checksum, 16) {
+        Ok(checksum) if checksum == crc32fast::hash(json.as_bytes()) => {
+            serde_json::from_str(json).ok()
+        }
+        _ => None,
+    }
+}
+
+fn storage<E: fmt::Display>(e: E) -> ErrorKind {
+    ErrorKind::Storage(format!("{}", e))
+}
+
+// The snapshot is first written next to its final location
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut temporary = OsString::from(path.as_os_str());
+    temporary.push(".tmp");
+    PathBuf::from(temporary)
+}
+
+// Error kind(s) used while persisting a Raffle
+#[derive(Debug)]
+pub enum PersistError {
+    Io(io::Error),
+    Corrupt(String),
+    Raffle(ErrorKind),
+}
+
+impl fmt::Display for PersistError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PersistError::Io(e) => write!(f, "I/O error: {}", e),
+            PersistError::Corrupt(reason) => write!(f, "Corrupt snapshot: {}", reason),
+            PersistError::Raffle(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+    use crate::models::{Game, Games, RuleSet};
+
+    // A snapshot path unique to each test, cleaned up before use
+    fn scratch(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("raffle-persist-{}-{}.json", name, process::id()));
+        let _ = fs::remove_file(&path);
+        let _ = fs::remove_file(temporary_path(&path));
+        path
+    }
+
+    #[test]
+    fn persist_save_load() {
+        let path = scratch("save-load");
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.new_ticket(Some(3), None, None).unwrap();
+        raffle.evaluate_ticket("2").unwrap();
+        let snapshot = raffle.snapshot().unwrap();
+
+        save(&path, &snapshot).unwrap();
+        assert!(!temporary_path(&path).exists());
+        assert_eq!(load(&path).unwrap(), Some(snapshot));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persist_load_missing() {
+        let path = scratch("missing");
+        assert!(load(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn persist_load_corrupt() {
+        let path = scratch("corrupt");
+        fs::write(&path, "{\"version\": 1, \"last_id\": ").unwrap();
+
+        match load(&path) {
+            Err(PersistError::Corrupt(_)) => (),
+            other => panic!("expected Corrupt, got {:?}", other),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persist_load_unknown_version() {
+        let path = scratch("version");
+        fs::write(&path, "{\"version\": 999, \"last_id\": 0, \"tickets\": []}").unwrap();
+
+        match load(&path) {
+            Err(PersistError::Corrupt(_)) => (),
+            other => panic!("expected Corrupt, got {:?}", other),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn persist_compact() {
+        let (path, journal) = (scratch("compact"), scratch("compact-journal"));
+        let raffle = SharedRaffle::new(Raffle::instantiate());
+        let (file, _) = FileJournal::open(&journal).unwrap();
+        raffle.write().unwrap().set_journal(Box::new(file)).unwrap();
+        raffle
+            .write()
+            .unwrap()
+            .new_ticket(None, None, None)
+            .unwrap();
+        assert!(fs::metadata(&journal).unwrap().len() > 0);
+
+        compact(&raffle, &path).unwrap();
+        let snapshot = load(&path).unwrap().unwrap();
+        assert_eq!(snapshot.last_id, 1);
+        assert_eq!(fs::metadata(&journal).unwrap().len(), 0);
+        fs::remove_file(&path).unwrap();
+        fs::remove_file(&journal).unwrap();
+    }
+
+    #[test]
+    fn persist_restore() {
+        let config = SnapshotConfig {
+            path: scratch("restore"),
+            interval: Duration::from_secs(60),
+            journal: scratch("restore-journal"),
+            ignore_corrupt: false,
+        };
+        let raffle = SharedRaffle::new(Raffle::instantiate());
+        restore(&mut raffle.write().unwrap(), &config).unwrap();
+        raffle
+            .write()
+            .unwrap()
+            .new_ticket(Some(2), None, None)
+            .unwrap();
+        compact(&raffle, &config.path).unwrap();
+        raffle
+            .write()
+            .unwrap()
+            .new_ticket(Some(3), None, None)
+            .unwrap();
+        raffle.write().unwrap().evaluate_ticket("1").unwrap();
+
+        let mut restored = Raffle::instantiate();
+        restore(&mut restored, &config).unwrap();
+        assert_eq!(
+            restored.snapshot().unwrap(),
+            raffle.read().unwrap().snapshot().unwrap()
+        );
+        assert_eq!(restored.new_ticket(None, None, None).unwrap(), "3");
+
+        fs::write(&config.path, "not a snapshot").unwrap();
+        assert!(restore(&mut Raffle::instantiate(), &config).is_err());
+        quarantine(&config).unwrap();
+        let mut fresh = Raffle::instantiate();
+        restore(&mut fresh, &config).unwrap();
+        assert_eq!(fresh.snapshot().unwrap().last_id, 0);
+
+        for path in &[&config.path, &config.journal] {
+            let mut aside = OsString::from(path.as_os_str());
+            aside.push(".corrupt");
+            fs::remove_file(aside).unwrap();
+        }
+        fs::remove_file(&config.journal).unwrap();
+    }
+
+    #[test]
+    fn persist_restore_rulesets() {
+        let config = SnapshotConfig {
+            path: scratch("restore-rulesets"),
+            interval: Duration::from_secs(60),
+            journal: scratch("restore-rulesets-journal"),
+            ignore_corrupt: false,
+        };
+        let mut raffle = Raffle::instantiate();
+        restore(&mut raffle, &config).unwrap();
+        raffle.new_ticket(Some(3), None, None).unwrap();
+
+        // Crashing before a snapshot is taken and coming back with different rules
+        // keeps the Ticket on the rules it was created under
+        let mut unpaid = Game::classic();
+        unpaid.rules = RuleSet::new(Vec::new());
+        let mut restarted = Raffle::instantiate();
+        restarted.set_games(Games::single(unpaid)).unwrap();
+        restore(&mut restarted, &config).unwrap();
+        assert_eq!(
+            restarted.get_ticket("1").unwrap(),
+            raffle.get_ticket("1").unwrap()
+        );
+        assert_eq!(restarted.get_games()[0].version, 2);
+        fs::remove_file(&config.journal).unwrap();
+    }
+
+    // Journals a few Events to [path], returning the Raffle they happened to
+    fn journaled_raffle(path: &Path) -> Raffle {
+        let (journal, records) = FileJournal::open(path).unwrap();
+        assert!(records.is_empty());
+        let mut raffle = Raffle::instantiate();
+        raffle.set_journal(Box::new(journal)).unwrap();
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.new_ticket(None, None, None).unwrap();
+        raffle.append_ticket("1", 3, None).unwrap();
+        raffle.evaluate_ticket("1").unwrap();
+        raffle
+    }
+
+    #[test]
+    fn FileJournal_replay() {
+        let path = scratch("journal-replay");
+        let raffle = journaled_raffle(&path);
+
+        let (_, records) = FileJournal::open(&path).unwrap();
+        // The rules the Tickets are pinned to come first
+        assert_eq!(records.len(), 5);
+        match &records[0] {
+            Record::Ruleset { ruleset } => assert_eq!(ruleset.version, 1),
+            other => panic!("expected Ruleset, got {:?}", other),
+        }
+        let mut replayed = Raffle::instantiate();
+        for record in records.iter().chain(records.iter()) {
+            match record.clone() {
+                Record::Event(event) => replayed.replay(*event).unwrap(),
+                Record::Ruleset { ruleset } => replayed.replay_ruleset(ruleset).unwrap(),
+            }
+        }
+        assert_eq!(replayed.snapshot().unwrap(), raffle.snapshot().unwrap());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn FileJournal_rollback() {
+        let path = scratch("journal-rollback");
+        journaled_raffle(&path);
+        let (mut journal, records) = FileJournal::open(&path).unwrap();
+        let length = fs::metadata(&path).unwrap().len();
+
+        match &records[1] {
+            Record::Event(event) => journal.record(event).unwrap(),
+            other => panic!("expected Event, got {:?}", other),
+        }
+        journal.rollback().unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().len(), length);
+        assert_eq!(FileJournal::open(&path).unwrap().1, records);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn FileJournal_failed_append() {
+        let path = scratch("journal-failed");
+        journaled_raffle(&path);
+        let (mut journal, records) = FileJournal::open(&path).unwrap();
+        let event = match &records[1] {
+            Record::Event(event) => event.clone(),
+            other => panic!("expected Event, got {:?}", other),
+        };
+
+        // A handle that can't be written to, or cut back, fails the append
+        let file = std::mem::replace(&mut journal.file, File::open(&path).unwrap());
+        assert!(journal.record(&event).is_err());
+        journal.file = file;
+        // As if the failed write had made it partway to disk
+        OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"0badf00d {\"seq\":")
+            .unwrap();
+
+        journal.record(&event).unwrap();
+        let (_, reopened) = FileJournal::open(&path).unwrap();
+        assert_eq!(reopened.len(), records.len() + 1);
+        assert_eq!(reopened[records.len()], Record::Event(event));
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn FileJournal_truncated_tail() {
+        let path = scratch("journal-torn");
+        journaled_raffle(&path);
+        let length = fs::metadata(&path).unwrap().len();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(length - 10).unwrap();
+
+        let (mut journal, records) = FileJournal::open(&path).unwrap();
+        assert_eq!(records.len(), 4);
+        match &records[1] {
+            Record::Event(event) => journal.record(event).unwrap(),
+            other => panic!("expected Event, got {:?}", other),
+        }
+        let (_, records) = FileJournal::open(&path).unwrap();
+        assert_eq!(records.len(), 5);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn FileJournal_damaged_record() {
+        let path = scratch("journal-damaged");
+        journaled_raffle(&path);
+        let mut contents = fs::read(&path).unwrap();
+        contents[20] ^= 0x01;
+        fs::write(&path, &contents).unwrap();
+
+        match FileJournal::open(&path) {
+            Err(PersistError::Corrupt(_)) => (),
+            other => panic!("expected Corrupt, got {:?}", other.map(|(_, e)| e)),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -1,139 +1,628 @@
 use {
-    crate::models::Raffle,
+    crate::models::{
+        self, ErrorKind, ExpiryPolicy, Game, GameView, Games, Limits, LineShape, Raffle, RuleSet,
+        SortKey, SqliteStore, StatusResult, TicketHistory, TicketPage, TicketQuery, TicketView,
+        TransitionLog,
+    },
+    crate::persist::{self, SnapshotConfig},
+    crate::shared::SharedRaffle,
+    crate::sweeper,
     rocket::{
+        fairing::AdHoc,
         http::{ContentType, Status},
-        request::Request,
+        request::{self, Form, FromRequest, Request},
         response::{self, Responder, Response as rResponse},
-        Rocket, State,
+        Config, Outcome, Rocket, State,
+    },
+    rocket_contrib::json::{Json, JsonError},
+    serde::{Deserialize, Serialize},
+    std::{
+        convert::TryFrom,
+        io::Cursor,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Duration,
     },
-    serde_json::{json, value::Value as jVal},
-    std::{io::Cursor, sync::RwLock},
 };
 
+// Pre-REST routes, kept mounted while clients migrate
+mod legacy;
+
+// Export and import of the whole Raffle, behind a token
+mod admin;
+
 pub fn rocket() -> Rocket {
+    assemble(rocket::ignite())
+}
+
+// Internal function for mounting the routes and state on a configured [rocket]
+fn assemble(rocket: Rocket) -> Rocket {
     // Start web server...
-    rocket::ignite()
+    let rocket = rocket
         .mount(
             // off host root...
             "/",
             // with the following routes...
             routes![
                 create_ticket,
-                create_ticket_with,
-                get_ticket_from,
+                get_games,
                 get_ticket_list,
+                get_ticket_from,
+                get_ticket_transitions,
+                get_ticket_history,
                 append_to_ticket,
+                void_ticket,
                 evaluate_ticket,
             ],
         )
-        // and this internal state
-        .manage(RwLock::new(Raffle::instantiate()))
+        // answering errors as problem+json
+        .register(catchers![
+            bad_request,
+            unauthorized,
+            not_found,
+            unprocessable,
+            internal_error
+        ])
+        // and this internal state, kept in the configured storage backend,
+        .attach(AdHoc::on_attach("Raffle storage", manage_raffle))
+        // restored from, and periodically saved to, disk if configured
+        .attach(AdHoc::on_attach("Raffle snapshots", restore_snapshot))
+        // and swept of stale tickets in the background, if an expiry policy is set
+        .attach(AdHoc::on_attach("Ticket sweeper", start_sweeper));
+
+    // Only offer the admin routes if there is a token to guard them with
+    let rocket = match rocket.config().get_str("admin_token").map(String::from) {
+        Ok(token) => rocket.manage(admin::AdminToken(token)).mount(
+            admin::BASE,
+            routes![
+                admin::export_raffle,
+                admin::import_raffle,
+                admin::rebuild_raffle
+            ],
+        ),
+        Err(_) => rocket,
+    };
+
+    // Keep the pre-REST GET routes around for clients that haven't migrated yet,
+    // unless the config explicitly turns them off
+    match rocket.config().get_bool("legacy_routes").unwrap_or(true) {
+        true => rocket.mount(
+            legacy::BASE,
+            routes![
+                legacy::create_ticket,
+                legacy::create_ticket_with,
+                legacy::get_ticket_from,
+                legacy::get_ticket_list,
+                legacy::append_to_ticket,
+                legacy::evaluate_ticket,
+            ],
+        ),
+        false => rocket,
+    }
 }
 
-// Aliases for easier readability
-type Internal<'r> = State<'r, RwLock<Raffle>>;
-type Response = Result<Good, Fail>;
+// Opens the configured storage backend and hands the Raffle built on it to Rocket,
+// a backend that can't be opened aborts launch
+fn manage_raffle(rocket: Rocket) -> Result<Rocket, Rocket> {
+    match open_raffle(rocket.config()) {
+        Ok(raffle) => Ok(rocket.manage(Arc::new(SharedRaffle::new(raffle)))),
+        Err(e) => {
+            eprintln!("Unable to open raffle: {}", e);
+            Err(rocket)
+        }
+    }
+}
 
-// Creates a new ticket with the default number of Lines [10]
-#[get("/ticket")]
-fn create_ticket(state: Internal) -> Response {
-    match state.write() {
-        Ok(mut raffle) => {
-            let ticket_id = raffle.new_ticket(None);
-            Ok(Good::Info(format!(
-                "Added ticket <{}> with [10] lines",
-                ticket_id
+// Builds a Raffle on the storage backend named by the config, tickets are kept
+// in memory unless told otherwise
+pub(crate) fn open_raffle(config: &Config) -> Result<Raffle, ErrorKind> {
+    let limits = line_limits(config);
+    let mut raffle = match config.get_str("storage").unwrap_or(DEFAULT_STORAGE) {
+        "memory" => Raffle::with_limits(limits),
+        "sqlite" => {
+            let path = config.get_str("sqlite_path").unwrap_or(DEFAULT_SQLITE_PATH);
+            let store = SqliteStore::open(Path::new(path))?;
+            Raffle::with_store(Box::new(store), limits)
+        }
+        other => {
+            return Err(ErrorKind::Storage(format!(
+                "unknown storage backend '{}', expected one of: memory, sqlite",
+                other
             )))
         }
-        Err(_) => Err(Fail::LockPoisoned),
+    };
+    // New tickets get ids from the configured scheme, old ones keep theirs
+    if let Ok(scheme) = config.get_str("ticket_ids") {
+        raffle.set_id_scheme(scheme.parse()?);
     }
+    raffle.set_expiry(expiry_policy(config)?);
+    raffle.set_games(games(config)?)?;
+    // Tickets stored before Events were recorded get a history starting here
+    raffle.adopt_untracked()?;
+    Ok(raffle)
 }
 
-// Creates a new ticket with a user defined number of Lines [lines]
-#[get("/ticket/<lines>")]
-fn create_ticket_with(state: Internal, lines: u64) -> Response {
-    match state.write() {
-        Ok(mut raffle) => {
-            let ticket_id = raffle.new_ticket(Some(lines));
-            Ok(Good::Info(format!(
-                "Added ticket <{}> with [{}] lines",
-                ticket_id, lines
+// Which storage backend is used, when the config doesn't say
+const DEFAULT_STORAGE: &str = "memory";
+
+// Where the SQLite database lives, when the config doesn't say
+const DEFAULT_SQLITE_PATH: &str = "raffle.db";
+
+// Loads the last snapshot and the journal written since into the managed Raffle
+// and keeps writing new ones, if the config names a snapshot file. Files that
+// can't be read abort launch, unless the config says to start without them instead
+fn restore_snapshot(rocket: Rocket) -> Result<Rocket, Rocket> {
+    let config = match snapshot_config(rocket.config()) {
+        Some(config) => config,
+        None => return Ok(rocket),
+    };
+    let raffle = match rocket.state::<Arc<SharedRaffle>>() {
+        Some(raffle) => raffle.clone(),
+        None => return Err(rocket),
+    };
+
+    let mut restored = match open_raffle(rocket.config()) {
+        Ok(restored) => restored,
+        Err(_) => return Err(rocket),
+    };
+    if let Err(e) = persist::restore(&mut restored, &config) {
+        eprintln!(
+            "Unable to restore raffle from {}: {}",
+            config.path.display(),
+            e
+        );
+        if !config.ignore_corrupt {
+            return Err(rocket);
+        }
+
+        eprintln!("Moving the damaged files aside and starting without them instead");
+        restored = match open_raffle(rocket.config()) {
+            Ok(restored) => restored,
+            Err(_) => return Err(rocket),
+        };
+        let fresh =
+            persist::quarantine(&config).and_then(|_| persist::restore(&mut restored, &config));
+        if let Err(e) = fresh {
+            eprintln!("Unable to start a new snapshot: {}", e);
+            return Err(rocket);
+        }
+    }
+    match raffle.write() {
+        Ok(mut raffle) => *raffle = restored,
+        Err(_) => return Err(rocket),
+    }
+
+    persist::spawn_writer(raffle.clone(), config.clone());
+    persist::snapshot_on_shutdown(raffle, config);
+    Ok(rocket)
+}
+
+// Reads the snapshot settings from the config, snapshots are only taken of the memory
+// store and if a snapshot_path is set. The journal sits next to it unless told otherwise
+pub(crate) fn snapshot_config(config: &Config) -> Option<SnapshotConfig> {
+    // Other backends are durable on their own, and restoring an older snapshot
+    // over them would roll their tickets back
+    if config.get_str("storage").unwrap_or(DEFAULT_STORAGE) != DEFAULT_STORAGE {
+        return None;
+    }
+    let path = config.get_str("snapshot_path").ok()?;
+    let interval = match config.get_int("snapshot_interval") {
+        Ok(n) if n > 0 => n as u64,
+        _ => DEFAULT_SNAPSHOT_INTERVAL,
+    };
+
+    let journal = match config.get_str("journal_path") {
+        Ok(journal) => PathBuf::from(journal),
+        Err(_) => PathBuf::from(format!("{}.journal", path)),
+    };
+
+    Some(SnapshotConfig {
+        path: PathBuf::from(path),
+        journal,
+        interval: Duration::from_secs(interval),
+        ignore_corrupt: config.get_bool("snapshot_ignore_corrupt").unwrap_or(false),
+    })
+}
+
+// Seconds between snapshots, when the config doesn't say
+const DEFAULT_SNAPSHOT_INTERVAL: u64 = 60;
+
+// Sweeps the managed Raffle every sweep_interval seconds, if its expiry policy
+// could ever change a ticket
+fn start_sweeper(rocket: Rocket) -> Result<Rocket, Rocket> {
+    match expiry_policy(rocket.config()) {
+        Ok(ref policy) if !policy.sweeps() => return Ok(rocket),
+        Ok(_) => (),
+        Err(_) => return Err(rocket),
+    }
+    let raffle = match rocket.state::<Arc<SharedRaffle>>() {
+        Some(raffle) => raffle.clone(),
+        None => return Err(rocket),
+    };
+
+    let interval = match rocket.config().get_int("sweep_interval") {
+        Ok(n) if n > 0 => n as u64,
+        _ => DEFAULT_SWEEP_INTERVAL,
+    };
+    sweeper::spawn_sweeper(raffle, Duration::from_secs(interval));
+    Ok(rocket)
+}
+
+// Reads the expiry policy from the config: open_ticket_ttl and checked_ticket_retention
+// in seconds, each unset by default, and expiry_action, expire unless told otherwise
+fn expiry_policy(config: &Config) -> Result<ExpiryPolicy, ErrorKind> {
+    let seconds = |key: &str| match config.get_int(key) {
+        Ok(n) if n >= 0 => Some(n as u64),
+        _ => None,
+    };
+    let on_ttl = match config.get_str("expiry_action") {
+        Ok(action) => action.parse()?,
+        Err(_) => ExpiryPolicy::default().on_ttl,
+    };
+
+    Ok(ExpiryPolicy {
+        open_ttl: seconds("open_ticket_ttl"),
+        on_ttl,
+        retention: seconds("checked_ticket_retention"),
+    })
+}
+
+// Seconds between sweeps, when the config doesn't say
+const DEFAULT_SWEEP_INTERVAL: u64 = 60;
+
+// Settings that tune the single game served when there is no games file
+const SINGLE_GAME_KEYS: &[&str] = &["rules_path", "line_width", "line_symbols"];
+
+// Loads the games named by games_path in the config. Without one only the classic
+// game is served, scored by the rules file at rules_path and drawn in the configured
+// line shape, if those are set
+fn games(config: &Config) -> Result<Games, ErrorKind> {
+    if let Ok(path) = config.get_str("games_path") {
+        let conflicting: Vec<String> = SINGLE_GAME_KEYS
+            .iter()
+            .filter(|key| config.extras.contains_key(**key))
+            .map(|key| format!("{} can't be set alongside games_path, set it per game", key))
+            .collect();
+        return match conflicting.is_empty() {
+            true => Games::load(Path::new(path)),
+            false => Err(ErrorKind::InvalidGames(conflicting)),
+        };
+    }
+
+    let mut game = Game::classic();
+    // Lines are scored by the classic rules unless a rules file says otherwise
+    if let Ok(path) = config.get_str("rules_path") {
+        game.rules = RuleSet::load(Path::new(path))?;
+    }
+    game.shape = line_shape(config)?;
+    Ok(Games::single(game))
+}
+
+// Reads the width and symbol count of new Lines from the config, falling back to
+// the classic game for either that isn't set
+fn line_shape(config: &Config) -> Result<LineShape, ErrorKind> {
+    let defaults = LineShape::default();
+    let width = config
+        .get_int("line_width")
+        .unwrap_or(defaults.width as i64);
+    let symbols = config
+        .get_int("line_symbols")
+        .unwrap_or(defaults.symbols as i64);
+
+    match (usize::try_from(width), u8::try_from(symbols)) {
+        (Ok(width), Ok(symbols)) => LineShape::new(width, symbols),
+        _ => Err(ErrorKind::InvalidShape(format!(
+            "line_width {} and line_symbols {} must both be positive, and line_symbols at most 255",
+            width, symbols
+        ))),
+    }
+}
+
+// Reads the line count limits from the config, falling back to the defaults
+// for any limit that isn't set
+fn line_limits(config: &Config) -> Limits {
+    let defaults = Limits::default();
+    let get = |key: &str, default: u64| match config.get_int(key) {
+        Ok(n) if n >= 0 => n as u64,
+        _ => default,
+    };
+
+    Limits {
+        min_per_request: get("min_lines_per_request", defaults.min_per_request),
+        max_per_request: get("max_lines_per_request", defaults.max_per_request),
+        max_per_ticket: get("max_lines_per_ticket", defaults.max_per_ticket),
+    }
+}
+
+// Aliases for easier readability
+type Internal<'r> = State<'r, Arc<SharedRaffle>>;
+type Response<T> = Result<Good<T>, Fail>;
+
+// Version of the response bodies' schema, bumped whenever their shape changes
+const SCHEMA_VERSION: u32 = 3;
+
+// Request body for creating a ticket in [game], which falls back to the default game,
+// [lines] falls back to the game's default
+#[derive(Debug, Deserialize)]
+struct NewTicket {
+    lines: Option<u64>,
+    game: Option<String>,
+}
+
+// Request body for amending a ticket with N [lines] more lines
+#[derive(Debug, Deserialize)]
+struct Amendment {
+    lines: u64,
+}
+
+// Query string for listing tickets, see TicketQuery for what each field does
+#[derive(Debug, FromForm)]
+struct ListQuery {
+    after: Option<String>,
+    limit: Option<u64>,
+    state: Option<String>,
+    voided: Option<bool>,
+    min_lines: Option<u64>,
+    min_score: Option<u64>,
+    max_score: Option<u64>,
+    sort: Option<String>,
+    order: Option<String>,
+}
+
+impl ListQuery {
+    fn into_query(self) -> Result<TicketQuery, ErrorKind> {
+        let defaults = TicketQuery::default();
+        let descending = match self.order.as_ref().map(String::as_str) {
+            None | Some("asc") => false,
+            Some("desc") => true,
+            Some(other) => {
+                return Err(ErrorKind::InvalidQuery(format!(
+                    "unknown order '{}', expected one of: asc, desc",
+                    other
+                )))
+            }
+        };
+
+        Ok(TicketQuery {
+            after: self.after,
+            limit: self.limit.unwrap_or(defaults.limit),
+            state: match self.state {
+                Some(state) => Some(state.parse::<models::State>()?),
+                None => None,
+            },
+            include_voided: self.voided.unwrap_or(defaults.include_voided),
+            min_lines: self.min_lines,
+            min_score: self.min_score,
+            max_score: self.max_score,
+            sort: match self.sort {
+                Some(sort) => sort.parse::<SortKey>()?,
+                None => defaults.sort,
+            },
+            descending,
+        })
+    }
+}
+
+// Response body for a newly created ticket
+#[derive(Debug, Serialize)]
+struct Created {
+    id: String,
+    game: String,
+    line_count: u64,
+}
+
+// Response body for an amended ticket
+#[derive(Debug, Serialize)]
+struct Amended {
+    id: String,
+    added: u64,
+    line_count: u64,
+}
+
+// Who a request was made on behalf of: whoever the X-Requester header names,
+// nobody in particular without one
+struct Requester(Option<String>);
+
+impl<'a, 'r> FromRequest<'a, 'r> for Requester {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let requester = request.headers().get_one("X-Requester").map(String::from);
+        Outcome::Success(Requester(requester))
+    }
+}
+
+impl Requester {
+    fn name(&self) -> Option<&str> {
+        self.0.as_ref().map(String::as_str)
+    }
+}
+
+// Creates a new ticket in either a user defined or the default game, with either
+// a user defined or the game's default number of Lines. Without a body the
+// ticket gets the defaults, a body that can't be read is refused
+#[post("/ticket", data = "<body>")]
+fn create_ticket(
+    state: Internal,
+    requester: Requester,
+    body: Option<Result<Json<NewTicket>, JsonError>>,
+) -> Response<Created> {
+    let (lines, game) = match body {
+        Some(Ok(body)) => {
+            let body = body.into_inner();
+            (body.lines, body.game)
+        }
+        Some(Err(JsonError::Parse(raw, _))) if raw.trim().is_empty() => (None, None),
+        Some(Err(_)) => {
+            return Err(Fail::BadRequest(String::from(
+                "malformed body: {\"lines\"?: unsigned integer, \"game\"?: string}",
             )))
         }
-        Err(_) => Err(Fail::LockPoisoned),
+        None => (None, None),
+    };
+    match state.write() {
+        Ok(mut raffle) => match raffle
+            .new_ticket(lines, game.as_ref().map(String::as_str), requester.name())
+            .and_then(|id| raffle.get_ticket(&id))
+        {
+            Ok(ticket) => Ok(Good::Created(Created {
+                id: ticket.id,
+                game: ticket.game,
+                line_count: ticket.line_count,
+            })),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
     }
 }
 
-// Returns the entire list of Tickets as Json
-#[get("/ticket/list")]
-fn get_ticket_list(state: Internal) -> Response {
+// Returns every game served, along with its line shape and rules
+#[get("/games")]
+fn get_games(state: Internal) -> Response<Vec<GameView>> {
     match state.read() {
-        Ok(raffle) => Ok(Good::Success(raffle.get_ticket_list())),
-        Err(_) => Err(Fail::LockPoisoned),
+        Ok(raffle) => Ok(Good::Success(raffle.get_games())),
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Returns a page of Tickets, filtered and sorted according to [query]
+#[get("/ticket?<query..>")]
+fn get_ticket_list(state: Internal, query: Form<ListQuery>) -> Response<TicketPage> {
+    let query = match query.into_inner().into_query() {
+        Ok(query) => query,
+        Err(e) => return Err(Fail::from(e)),
+    };
+    match state.read() {
+        Ok(raffle) => match raffle.query_tickets(&query) {
+            Ok(page) => Ok(Good::Success(page)),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
     }
 }
 
 // Returns a user defined Ticket via its id [id]
-#[get("/ticket/list/<id>")]
-fn get_ticket_from(state: Internal, id: u64) -> Response {
+#[get("/ticket/<id>")]
+fn get_ticket_from(state: Internal, id: String) -> Response<TicketView> {
     match state.read() {
-        Ok(raffle) => match raffle.get_ticket(id) {
+        Ok(raffle) => match raffle.get_ticket(&id) {
             Ok(ticket) => Ok(Good::Success(ticket)),
-            Err(e) => Err(Fail::Unprocessable(format!("{}", e))),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Returns the lifecycle state and recorded transitions of a Ticket via its id [id]
+#[get("/ticket/<id>/transitions")]
+fn get_ticket_transitions(state: Internal, id: String) -> Response<TransitionLog> {
+    match state.read() {
+        Ok(raffle) => match raffle.get_transitions(&id) {
+            Ok(transitions) => Ok(Good::Success(transitions)),
+            Err(e) => Err(Fail::from(e)),
         },
-        Err(_) => Err(Fail::LockPoisoned),
+        Err(e) => Err(Fail::from(e)),
     }
 }
 
-// Appends a user defined number of Lines [append] to a Ticket via its id [id]
-#[get("/ticket/append/<id>?<lines>")]
-fn append_to_ticket(state: Internal, id: u64, lines: Option<u64>) -> Response {
-    match (lines, state.write()) {
-        (Some(lines), Ok(mut raffle)) => match raffle.append_ticket(id, lines) {
-            Ok(_) => Ok(Good::Info(format!(
-                "Appended [{}] lines to ticket <{}>",
-                lines, id
-            ))),
-            Err(e) => Err(Fail::Unprocessable(format!("{}", e))),
+// Returns every Event that happened to a Ticket via its id [id], oldest first
+#[get("/ticket/<id>/history")]
+fn get_ticket_history(state: Internal, id: String) -> Response<TicketHistory> {
+    match state.read() {
+        Ok(raffle) => match raffle.get_history(&id) {
+            Ok(history) => Ok(Good::Success(history)),
+            Err(e) => Err(Fail::from(e)),
         },
-        (None, _) => Err(Fail::BadRequest),
-        (_, Err(_)) => Err(Fail::LockPoisoned),
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Amends a Ticket via its id [id] with a user defined number of Lines
+#[put("/ticket/<id>", data = "<body>")]
+fn append_to_ticket(
+    state: Internal,
+    id: String,
+    requester: Requester,
+    body: Option<Json<Amendment>>,
+) -> Response<Amended> {
+    match (body, state.write()) {
+        (Some(body), Ok(mut raffle)) => {
+            let added = body.into_inner().lines;
+            match raffle.append_ticket(&id, added, requester.name()) {
+                Ok(line_count) => Ok(Good::Success(Amended {
+                    id,
+                    added,
+                    line_count,
+                })),
+                Err(e) => Err(Fail::from(e)),
+            }
+        }
+        (None, _) => Err(Fail::BadRequest(String::from(
+            "malformed body: {\"lines\": unsigned integer}",
+        ))),
+        (_, Err(e)) => Err(Fail::from(e)),
     }
 }
 
-// Uses up a Ticket via its id [id] and returns its score
-#[get("/eval/<id>")]
-fn evaluate_ticket(state: Internal, id: u64) -> Response {
+// Voids a Ticket via its id [id], it can still be retrieved but no longer used
+#[delete("/ticket/<id>")]
+fn void_ticket(state: Internal, id: String) -> Response<TicketView> {
     match state.write() {
-        Ok(mut raffle) => match raffle.evaluate_ticket(id) {
+        Ok(mut raffle) => match raffle.void_ticket(&id) {
             Ok(ticket) => Ok(Good::Success(ticket)),
-            Err(e) => Err(Fail::Unprocessable(format!("{}", e))),
+            Err(e) => Err(Fail::from(e)),
         },
-        Err(_) => Err(Fail::LockPoisoned),
+        Err(e) => Err(Fail::from(e)),
     }
 }
 
-// Successful responses
+// Checks the status of a Ticket via its id [id] and returns its score
+#[put("/status/<id>")]
+fn evaluate_ticket(state: Internal, id: String) -> Response<StatusResult> {
+    match state.write() {
+        Ok(mut raffle) => match raffle.evaluate_ticket(&id) {
+            Ok(result) => Ok(Good::Success(result)),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Successful responses, wrapping a serializable body
 #[derive(Debug)]
-enum Good {
-    Info(String),
-    Success(jVal),
+enum Good<T> {
+    Success(T),
+    Created(T),
+}
+
+// Every successful response body is sent inside this envelope
+#[derive(Debug, Serialize)]
+struct Envelope<T> {
+    version: u32,
+    code: u16,
+    data: T,
 }
 
 // Custom implementation for API responses
-impl<'r> Responder<'r> for Good {
+impl<'r, T: Serialize> Responder<'r> for Good<T> {
     fn respond_to(self, _: &Request) -> response::Result<'r> {
-        match self {
-            Good::Info(i) => rResponse::build()
-                .sized_body(Cursor::new(json!({"code": 200, "info": i}).to_string()))
-                .header(ContentType::JSON)
-                .status(Status::Ok)
-                .ok(),
-            Good::Success(value) => rResponse::build()
-                .sized_body(Cursor::new(json!({"code": 200, "data": value}).to_string()))
+        let (status, data) = match self {
+            Good::Success(data) => (Status::Ok, data),
+            Good::Created(data) => (Status::Created, data),
+        };
+        let envelope = Envelope {
+            version: SCHEMA_VERSION,
+            code: status.code,
+            data,
+        };
+
+        match serde_json::to_string(&envelope) {
+            Ok(body) => rResponse::build()
+                .sized_body(Cursor::new(body))
                 .header(ContentType::JSON)
-                .status(Status::Ok)
+                .status(status)
                 .ok(),
+            Err(_) => Err(Status::InternalServerError),
         }
     }
 }
@@ -141,34 +630,206 @@ impl<'r> Responder<'r> for Good {
 // Errored responses
 #[derive(Debug)]
 enum Fail {
-    Unprocessable(String),
-    BadRequest,
-    LockPoisoned,
+    Raffle(ErrorKind),
+    BadRequest(String),
+}
+
+impl From<ErrorKind> for Fail {
+    fn from(err: ErrorKind) -> Self {
+        Fail::Raffle(err)
+    }
+}
+
+impl Fail {
+    // Maps each failure onto its status, problem type and title
+    fn classify(&self) -> (Status, &'static str, &'static str) {
+        match self {
+            Fail::Raffle(ErrorKind::TicketNotFound(_)) => {
+                (Status::NotFound, "ticket-not-found", "Ticket not found")
+            }
+            Fail::Raffle(ErrorKind::TicketAlreadyChecked(_)) => (
+                Status::Conflict,
+                "ticket-already-checked",
+                "Ticket already checked",
+            ),
+            Fail::Raffle(ErrorKind::TicketVoided(_)) => {
+                (Status::Conflict, "ticket-voided", "Ticket voided")
+            }
+            Fail::Raffle(ErrorKind::TicketExpired(_)) => {
+                (Status::Gone, "ticket-expired", "Ticket expired")
+            }
+            Fail::Raffle(ErrorKind::IllegalTransition(..)) => (
+                Status::Conflict,
+                "illegal-transition",
+                "Illegal ticket state transition",
+            ),
+            Fail::Raffle(ErrorKind::InvalidLineCount(..)) => (
+                Status::BadRequest,
+                "invalid-line-count",
+                "Invalid line count",
+            ),
+            Fail::Raffle(ErrorKind::InvalidQuery(_)) => {
+                (Status::BadRequest, "invalid-query", "Invalid query")
+            }
+            Fail::Raffle(ErrorKind::InvalidConfig(_)) => (
+                Status::InternalServerError,
+                "invalid-config",
+                "Invalid configuration",
+            ),
+            Fail::Raffle(ErrorKind::Storage(_)) => (
+                Status::ServiceUnavailable,
+                "storage-unavailable",
+                "Storage unavailable",
+            ),
+            Fail::Raffle(ErrorKind::InvalidImport(_)) => (
+                Status::UnprocessableEntity,
+                "invalid-import",
+                "Invalid import",
+            ),
+            Fail::Raffle(ErrorKind::InvalidRules(_)) => (
+                Status::InternalServerError,
+                "invalid-rules",
+                "Invalid rules",
+            ),
+            Fail::Raffle(ErrorKind::InvalidShape(_)) => (
+                Status::InternalServerError,
+                "invalid-line-shape",
+                "Invalid line shape",
+            ),
+            Fail::Raffle(ErrorKind::InvalidGames(_)) => (
+                Status::InternalServerError,
+                "invalid-games",
+                "Invalid games",
+            ),
+            Fail::Raffle(ErrorKind::UnknownGame(_)) => {
+                (Status::BadRequest, "unknown-game", "Unknown game")
+            }
+            Fail::Raffle(ErrorKind::RulesetConflict(_)) => {
+                (Status::Conflict, "ruleset-conflict", "Ruleset conflict")
+            }
+            Fail::BadRequest(_) => (Status::BadRequest, "invalid-input", "Invalid input"),
+        }
+    }
+
+    fn detail(self) -> String {
+        match self {
+            Fail::Raffle(err) => format!("{}", err),
+            Fail::BadRequest(err) => err,
+        }
+    }
 }
 
 // Custom implementation for API specific errors
 impl<'r> Responder<'r> for Fail {
+    fn respond_to(self, req: &Request) -> response::Result<'r> {
+        let (status, kind, title) = self.classify();
+        Problem::new(status, kind, title, self.detail(), req).respond_to(req)
+    }
+}
+
+// An RFC 7807 problem details body, used for every error the API returns
+#[derive(Debug, Serialize)]
+struct Problem {
+    #[serde(rename = "type")]
+    kind: String,
+    title: &'static str,
+    status: u16,
+    detail: String,
+    instance: String,
+}
+
+impl Problem {
+    fn new(
+        status: Status,
+        kind: &'static str,
+        title: &'static str,
+        detail: String,
+        req: &Request,
+    ) -> Self {
+        Problem {
+            kind: format!("{}/{}", PROBLEM_BASE, kind),
+            title,
+            status: status.code,
+            detail,
+            instance: req.uri().to_string(),
+        }
+    }
+}
+
+impl<'r> Responder<'r> for Problem {
     fn respond_to(self, _: &Request) -> response::Result<'r> {
-        match self {
-            Fail::Unprocessable(err) => rResponse::build()
-                .sized_body(Cursor::new(json!({"code": 422, "info": err}).to_string()))
-                .header(ContentType::JSON)
-                .status(Status::UnprocessableEntity)
-                .ok(),
-            Fail::BadRequest => rResponse::build()
-                .sized_body(Cursor::new(json!({"code": 400, "info": "malformed query: [append={{unsigned integer}}]"}).to_string()))
-                .header(ContentType::JSON)
-                .status(Status::BadRequest)
-                .ok(),
-            Fail::LockPoisoned => rResponse::build()
-                .sized_body(Cursor::new(json!({"code": 503, "info": "Unrecoverable error: Internal state poisoned, restart the server"}).to_string()))
-                .header(ContentType::JSON)
-                .status(Status::ServiceUnavailable)
+        let status = Status::from_code(self.status).unwrap_or(Status::InternalServerError);
+        match serde_json::to_string(&self) {
+            Ok(body) => rResponse::build()
+                .sized_body(Cursor::new(body))
+                .header(ContentType::new("application", "problem+json"))
+                .status(status)
                 .ok(),
+            Err(_) => Err(Status::InternalServerError),
         }
     }
 }
 
+// Where the problem types are documented, see assets/api.json
+const PROBLEM_BASE: &str = "/problems";
+
+// Replace rocket's default HTML error pages with problem details
+
+#[catch(400)]
+fn bad_request(req: &Request) -> Problem {
+    Problem::new(
+        Status::BadRequest,
+        "invalid-input",
+        "Invalid input",
+        String::from("The request could not be understood"),
+        req,
+    )
+}
+
+#[catch(401)]
+fn unauthorized(req: &Request) -> Problem {
+    Problem::new(
+        Status::Unauthorized,
+        "unauthorized",
+        "Unauthorized",
+        String::from("This resource requires a valid admin token"),
+        req,
+    )
+}
+
+#[catch(404)]
+fn not_found(req: &Request) -> Problem {
+    Problem::new(
+        Status::NotFound,
+        "not-found",
+        "Not found",
+        format!("No resource matches {} {}", req.method(), req.uri()),
+        req,
+    )
+}
+
+#[catch(422)]
+fn unprocessable(req: &Request) -> Problem {
+    Problem::new(
+        Status::UnprocessableEntity,
+        "invalid-input",
+        "Invalid input",
+        String::from("The request was well formed but could not be processed"),
+        req,
+    )
+}
+
+#[catch(500)]
+fn internal_error(req: &Request) -> Problem {
+    Problem::new(
+        Status::InternalServerError,
+        "internal-error",
+        "Internal server error",
+        String::from("The server failed to handle the request"),
+        req,
+    )
+}
+
 /*
 Code
 -------------------------------------------------------------------------------
@@ -179,14 +840,643 @@ Tests
 mod tests {
     #![allow(non_snake_case)]
     use super::rocket;
-    use rocket::http::Status;
-    use rocket::local::Client;
-    use serde_json::json;
+    use rocket::http::{ContentType, Header, Status};
+    use rocket::local::{Client, LocalResponse};
+    use serde_json::{json, Value};
+
+    fn body_of(response: &mut LocalResponse) -> Value {
+        serde_json::from_str(&response.body_string().expect("Response body")).expect("Json body")
+    }
+
+    #[test]
+    fn Route_storage_backend() {
+        use rocket::config::{Config, Environment};
+        let config = |storage: &str| {
+            Config::build(Environment::Development)
+                .extra("storage", storage)
+                .extra("sqlite_path", ":memory:")
+                .finalize()
+                .expect("Valid config")
+        };
+
+        assert!(super::open_raffle(&config("memory")).is_ok());
+        assert!(super::open_raffle(&config("sqlite")).is_ok());
+        assert!(super::open_raffle(&config("floppy")).is_err());
+    }
+
+    #[test]
+    fn Route_expiry_policy() {
+        use rocket::config::{Config, Environment};
+        let config = Config::build(Environment::Development)
+            .extra("open_ticket_ttl", 3600)
+            .extra("expiry_action", "check")
+            .finalize()
+            .expect("Valid config");
+        let policy = super::expiry_policy(&config).expect("Valid policy");
+        assert_eq!(policy.open_ttl, Some(3600));
+        assert_eq!(policy.on_ttl, crate::models::TtlAction::Check);
+        assert_eq!(policy.retention, None);
+
+        let config = Config::build(Environment::Development)
+            .extra("expiry_action", "delete")
+            .finalize()
+            .expect("Valid config");
+        assert!(super::expiry_policy(&config).is_err());
+        assert!(super::open_raffle(&config).is_err());
+    }
+
+    #[test]
+    fn Route_rules() {
+        use rocket::config::{Config, Environment};
+        let config = |path: &str| {
+            Config::build(Environment::Development)
+                .extra("rules_path", path)
+                .finalize()
+                .expect("Valid config")
+        };
+
+        assert!(super::open_raffle(&config("rules.toml")).is_ok());
+
+        let path = std::env::temp_dir().join("raffle-route-rules.toml");
+        std::fs::write(&path, "[[rule]]\nname = \"half done\"\n").unwrap();
+        for path in [path.to_str().unwrap(), "missing.toml"].iter() {
+            match super::open_raffle(&config(path)) {
+                Err(crate::models::ErrorKind::InvalidRules(_)) => (),
+                Err(e) => panic!("expected InvalidRules, got {}", e),
+                Ok(_) => panic!("expected InvalidRules"),
+            }
+        }
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn Route_games() {
+        use rocket::config::{Config, Environment};
+        let config = |extras: &[(&str, &str)]| {
+            extras
+                .iter()
+                .fold(
+                    Config::build(Environment::Development),
+                    |config, (key, value)| config.extra(key, *value),
+                )
+                .finalize()
+                .expect("Valid config")
+        };
+
+        let raffle = super::open_raffle(&config(&[("games_path", "games.toml")])).unwrap();
+        assert_eq!(raffle.get_games().len(), 2);
+        let raffle = super::open_raffle(&config(&[])).unwrap();
+        assert_eq!(raffle.get_games().len(), 1);
+
+        // Single game settings would be silently ignored next to a games file
+        match super::open_raffle(&config(&[
+            ("games_path", "games.toml"),
+            ("rules_path", "rules.toml"),
+        ])) {
+            Err(crate::models::ErrorKind::InvalidGames(problems)) => {
+                assert!(problems[0].starts_with("rules_path"))
+            }
+            Err(e) => panic!("expected InvalidGames, got {}", e),
+            Ok(_) => panic!("expected InvalidGames"),
+        }
+        assert!(super::open_raffle(&config(&[("games_path", "missing.toml")])).is_err());
+    }
+
+    #[test]
+    fn Route_line_shape() {
+        use crate::models::LineShape;
+        use rocket::config::{Config, Environment};
+        let config = |width: i64, symbols: i64| {
+            Config::build(Environment::Development)
+                .extra("line_width", width)
+                .extra("line_symbols", symbols)
+                .finalize()
+                .expect("Valid config")
+        };
+
+        let default = Config::build(Environment::Development)
+            .finalize()
+            .expect("Valid config");
+        assert_eq!(super::line_shape(&default).unwrap(), LineShape::default());
+        assert_eq!(
+            super::line_shape(&config(5, 5)).unwrap(),
+            LineShape::new(5, 5).unwrap()
+        );
+        for (width, symbols) in [(0, 3), (-1, 3), (3, 0), (3, 256)].iter() {
+            match super::open_raffle(&config(*width, *symbols)) {
+                Err(crate::models::ErrorKind::InvalidShape(_)) => (),
+                Err(e) => panic!("expected InvalidShape, got {}", e),
+                Ok(_) => panic!("expected InvalidShape"),
+            }
+        }
+    }
+
+    #[test]
+    fn Route_ticket_ids() {
+        use rocket::config::{Config, Environment};
+        let config = |scheme: &str| {
+            Config::build(Environment::Development)
+                .extra("ticket_ids", scheme)
+                .finalize()
+                .expect("Valid config")
+        };
+
+        let mut raffle = super::open_raffle(&config("uuid")).expect("Valid raffle");
+        assert_eq!(
+            raffle.new_ticket(None, None, None).ok().map(|id| id.len()),
+            Some(36)
+        );
+        assert!(super::open_raffle(&config("sequential")).is_ok());
+        assert!(super::open_raffle(&config("guid")).is_err());
+    }
 
     #[test]
     fn Route_create_ticket() {
         let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.post("/ticket").dispatch();
+        assert_eq!(response.status(), Status::Created);
+        assert_eq!(
+            body_of(&mut response),
+            json!({"version": 3, "code": 201, "data": {"id": "1", "game": "classic", "line_count": 10}})
+        );
+    }
+
+    #[test]
+    fn Route_create_ticket_with() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client
+            .post("/ticket")
+            .header(ContentType::JSON)
+            .body(json!({"lines": 5}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        assert_eq!(
+            body_of(&mut response),
+            json!({"version": 3, "code": 201, "data": {"id": "1", "game": "classic", "line_count": 5}})
+        );
+    }
+
+    #[test]
+    fn Route_create_ticket_malformed() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        for body in &["{\"lines\": ", "{\"lines\": \"five\"}"] {
+            let mut response = client
+                .post("/ticket")
+                .header(ContentType::JSON)
+                .body(*body)
+                .dispatch();
+            assert_eq!(response.status(), Status::BadRequest);
+            assert_eq!(
+                body_of(&mut response)["type"],
+                json!("/problems/invalid-input")
+            );
+        }
+
+        // Nothing was created along the way
+        let response = client.get("/ticket/1").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn Route_create_ticket_in_game() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client
+            .post("/ticket")
+            .header(ContentType::JSON)
+            .body(json!({"game": "jackpot"}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Created);
+        assert_eq!(
+            body_of(&mut response),
+            json!({"version": 3, "code": 201, "data": {"id": "1", "game": "jackpot", "line_count": 5}})
+        );
+
+        let mut response = client.get("/ticket/1").dispatch();
+        let body = body_of(&mut response);
+        assert_eq!(body["data"]["game"], json!("jackpot"));
+        assert_eq!(
+            body["data"]["lines"][0]["numbers"]
+                .as_array()
+                .unwrap()
+                .len(),
+            4
+        );
+
+        let mut response = client
+            .post("/ticket")
+            .header(ContentType::JSON)
+            .body(json!({"game": "bingo"}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(
+            body_of(&mut response)["type"],
+            json!("/problems/unknown-game")
+        );
+    }
+
+    #[test]
+    fn Route_get_games() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.get("/games").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let body = body_of(&mut response);
+        let games = body["data"].as_array().unwrap();
+        assert_eq!(games.len(), 2);
+        assert_eq!(games[0]["name"], json!("classic"));
+        assert_eq!(games[0]["default"], json!(true));
+        assert_eq!(
+            games[0]["rules"][0],
+            json!({"condition": "the numbers add up to 2", "payout": 10})
+        );
+        assert_eq!(games[1]["name"], json!("jackpot"));
+        assert_eq!(games[1]["line_width"], json!(4));
+    }
+
+    #[test]
+    fn Route_get_ticket_list() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.get("/ticket").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "version": 3,
+                "code": 200,
+                "data": {"tickets": [], "next": null, "total": 0},
+            })
+        );
+    }
+
+    #[test]
+    fn Route_get_ticket_from() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        let mut response = client.get("/ticket/1").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = body_of(&mut response);
+        assert_eq!(body["data"]["id"], json!("1"));
+        assert_eq!(body["data"]["state"], json!("open"));
+        assert_eq!(body["data"]["line_count"], json!(10));
+        assert_eq!(body["data"]["lines"].as_array().map(|l| l.len()), Some(10));
+    }
+
+    #[test]
+    fn Route_get_ticket_batches() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client
+            .post("/ticket")
+            .header(Header::new("X-Requester", "alice"))
+            .dispatch();
+        // The client's address is never recorded in place of a name
+        client
+            .put("/ticket/1")
+            .header(ContentType::JSON)
+            .remote("127.0.0.1:8000".parse().unwrap())
+            .body(json!({"lines": 5}).to_string())
+            .dispatch();
+        let mut response = client.get("/ticket/1").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = body_of(&mut response);
+        assert!(body["data"]["created_at"].is_u64());
+        assert!(body["data"]["updated_at"].is_u64());
+        assert_eq!(body["data"]["checked_at"], json!(null));
+
+        let batches = &body["data"]["batches"];
+        assert_eq!(batches[0]["start"], json!(0));
+        assert_eq!(batches[0]["end"], json!(10));
+        assert_eq!(batches[0]["requester"], json!("alice"));
+        assert_eq!(batches[1]["start"], json!(10));
+        assert_eq!(batches[1]["end"], json!(15));
+        assert_eq!(batches[1]["requester"], json!(null));
+    }
+
+    #[test]
+    fn Route_get_ticket_from_failure() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.get("/ticket/1").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "type": "/problems/ticket-not-found",
+                "title": "Ticket not found",
+                "status": 404,
+                "detail": "Ticket id: 1 doesn't exist",
+                "instance": "/ticket/1",
+            })
+        );
+    }
+
+    #[test]
+    fn Route_append_to_ticket_success() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        let mut response = client
+            .put("/ticket/1")
+            .header(ContentType::JSON)
+            .body(json!({"lines": 10}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            body_of(&mut response),
+            json!({"version": 3, "code": 200, "data": {"id": "1", "added": 10, "line_count": 20}})
+        );
+    }
+
+    #[test]
+    fn Route_append_to_ticket_failure() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client
+            .put("/ticket/1")
+            .header(ContentType::JSON)
+            .body(json!({"lines": 10}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "type": "/problems/ticket-not-found",
+                "title": "Ticket not found",
+                "status": 404,
+                "detail": "Ticket id: 1 doesn't exist",
+                "instance": "/ticket/1",
+            })
+        );
+    }
+
+    #[test]
+    fn Route_append_to_ticket_malformed() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        let mut response = client
+            .put("/ticket/1")
+            .header(ContentType::JSON)
+            .body(json!({"append": 10}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(
+            response.content_type(),
+            Some(ContentType::new("application", "problem+json"))
+        );
+        assert_eq!(
+            body_of(&mut response)["type"],
+            json!("/problems/invalid-input")
+        );
+    }
+
+    #[test]
+    fn Route_evaluate_ticket_failure() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.put("/status/1").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "type": "/problems/ticket-not-found",
+                "title": "Ticket not found",
+                "status": 404,
+                "detail": "Ticket id: 1 doesn't exist",
+                "instance": "/status/1",
+            })
+        );
+    }
+
+    #[test]
+    fn Route_evaluate_ticket_keeps_ticket() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        let mut response = client.put("/status/1").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = body_of(&mut response);
+        assert_eq!(body["version"], json!(3));
+        assert_eq!(body["data"]["id"], json!("1"));
+        assert!(body["data"]["score"].is_u64());
+        assert!(body["data"]["message"].is_string());
+        assert_eq!(body["data"]["lines"][0]["outcome"], json!(10));
+        assert_eq!(client.get("/ticket/1").dispatch().status(), Status::Ok);
+    }
+
+    #[test]
+    fn Route_append_to_checked_ticket() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        client.put("/status/1").dispatch();
+        let mut response = client
+            .put("/ticket/1")
+            .header(ContentType::JSON)
+            .body(json!({"lines": 10}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "type": "/problems/ticket-already-checked",
+                "title": "Ticket already checked",
+                "status": 409,
+                "detail": "Ticket id: 1 has already been checked",
+                "instance": "/ticket/1",
+            })
+        );
+    }
+
+    #[test]
+    fn Route_get_ticket_transitions() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        client.put("/status/1").dispatch();
+        let mut response = client.get("/ticket/1/transitions").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = body_of(&mut response);
+        assert_eq!(body["data"]["state"], json!("checked"));
+        assert_eq!(body["data"]["transitions"][1]["from"], json!("open"));
+    }
+
+    #[test]
+    fn Route_get_ticket_event_history() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        client
+            .put("/ticket/1")
+            .header(ContentType::JSON)
+            .body(json!({"lines": 2}).to_string())
+            .dispatch();
+        client.put("/status/1").dispatch();
+        let mut response = client.get("/ticket/1/history").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = body_of(&mut response);
+        assert_eq!(body["data"]["id"], json!("1"));
+        let events: Vec<&Value> = body["data"]["events"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|event| &event["event"])
+            .collect();
+        assert_eq!(
+            events,
+            vec!["ticket_created", "lines_appended", "ticket_checked"]
+        );
+        assert_eq!(
+            body["data"]["events"][1]["lines"].as_array().unwrap().len(),
+            2
+        );
+
+        let response = client.get("/ticket/2/history").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn Route_void_ticket() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        assert_eq!(client.delete("/ticket/1").dispatch().status(), Status::Ok);
+
+        let mut response = client.get("/ticket/1").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(body_of(&mut response)["data"]["state"], json!("voided"));
+
         let mut response = client.get("/ticket").dispatch();
+        assert_eq!(body_of(&mut response)["data"]["tickets"], json!([]));
+        let mut response = client.get("/ticket?voided=true").dispatch();
+        assert_eq!(
+            body_of(&mut response)["data"]["tickets"][0]["id"],
+            json!("1")
+        );
+
+        assert_eq!(
+            client.put("/status/1").dispatch().status(),
+            Status::Conflict
+        );
+    }
+
+    #[test]
+    fn Route_void_checked_ticket() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        client.put("/status/1").dispatch();
+        let mut response = client.delete("/ticket/1").dispatch();
+        assert_eq!(response.status(), Status::Conflict);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "type": "/problems/ticket-already-checked",
+                "title": "Ticket already checked",
+                "status": 409,
+                "detail": "Ticket id: 1 has already been checked",
+                "instance": "/ticket/1",
+            })
+        );
+    }
+
+    #[test]
+    fn Route_mutations_reject_get() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.get("/status/1").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "type": "/problems/not-found",
+                "title": "Not found",
+                "status": 404,
+                "detail": "No resource matches GET /status/1",
+                "instance": "/status/1",
+            })
+        );
+    }
+
+    #[test]
+    fn Route_create_ticket_invalid_line_count() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client
+            .post("/ticket")
+            .header(ContentType::JSON)
+            .body(json!({"lines": 0}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "type": "/problems/invalid-line-count",
+                "title": "Invalid line count",
+                "status": 400,
+                "detail": "Line count: 0 must be between 1 and 1000",
+                "instance": "/ticket",
+            })
+        );
+
+        let response = client
+            .post("/ticket")
+            .header(ContentType::JSON)
+            .body(json!({"lines": u64::max_value()}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn Route_append_to_ticket_invalid_line_count() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        let response = client
+            .put("/ticket/1")
+            .header(ContentType::JSON)
+            .body(json!({"lines": 1001}).to_string())
+            .dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    #[test]
+    fn Route_get_ticket_list_paged() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        (1..=3).for_each(|lines| {
+            client
+                .post("/ticket")
+                .header(ContentType::JSON)
+                .body(json!({ "lines": lines }).to_string())
+                .dispatch();
+        });
+
+        let mut response = client
+            .get("/ticket?limit=2&sort=lines&order=desc")
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = body_of(&mut response);
+        assert_eq!(body["data"]["total"], json!(3));
+        assert_eq!(body["data"]["tickets"][0]["id"], json!("3"));
+        assert_eq!(body["data"]["tickets"][1]["id"], json!("2"));
+        assert_eq!(body["data"]["next"], json!("2.2"));
+
+        let mut response = client
+            .get("/ticket?limit=2&sort=lines&order=desc&after=2.2")
+            .dispatch();
+        let body = body_of(&mut response);
+        assert_eq!(body["data"]["tickets"][0]["id"], json!("1"));
+        assert_eq!(body["data"]["next"], json!(null));
+
+        let mut response = client.get("/ticket?min_lines=2").dispatch();
+        assert_eq!(body_of(&mut response)["data"]["total"], json!(2));
+    }
+
+    #[test]
+    fn Route_get_ticket_list_invalid_query() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.get("/ticket?sort=color").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        assert_eq!(
+            body_of(&mut response)["type"],
+            json!("/problems/invalid-query")
+        );
+        let response = client.get("/ticket?state=lost").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+        let response = client.get("/ticket?after=nowhere").dispatch();
+        assert_eq!(response.status(), Status::BadRequest);
+    }
+
+    // Legacy route tests
+    #[test]
+    fn Route_legacy_create_ticket() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.get("/legacy/ticket").dispatch();
         assert_eq!(response.status(), Status::Ok);
         assert_eq!(
             response.body_string(),
@@ -195,9 +1485,9 @@ mod tests {
     }
 
     #[test]
-    fn Route_create_ticket_with() {
+    fn Route_legacy_create_ticket_with() {
         let client = Client::new(rocket()).expect("Valid rocket instance");
-        let mut response = client.get("/ticket/5").dispatch();
+        let mut response = client.get("/legacy/ticket/5").dispatch();
         assert_eq!(response.status(), Status::Ok);
         assert_eq!(
             response.body_string(),
@@ -206,9 +1496,21 @@ mod tests {
     }
 
     #[test]
-    fn Route_get_ticket_list() {
+    fn Route_legacy_get_ticket_list() {
         let client = Client::new(rocket()).expect("Valid rocket instance");
-        let mut response = client.get("/ticket/list").dispatch();
+        client.get("/legacy/ticket").dispatch();
+        client.get("/legacy/ticket/1").dispatch();
+        let mut response = client.get("/legacy/ticket/list").dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let body = body_of(&mut response);
+        assert_eq!(body["code"], json!(200));
+        assert_eq!(body["data"][1]["line_count"], json!(1));
+    }
+
+    #[test]
+    fn Route_legacy_get_ticket_list_empty() {
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let mut response = client.get("/legacy/ticket/list").dispatch();
         assert_eq!(response.status(), Status::Ok);
         assert_eq!(
             response.body_string(),
@@ -217,35 +1519,184 @@ mod tests {
     }
 
     #[test]
-    fn Route_get_ticket_from_failure() {
+    fn Route_legacy_get_ticket_from_failure() {
         let client = Client::new(rocket()).expect("Valid rocket instance");
-        let mut response = client.get("/ticket/list/1").dispatch();
-        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let mut response = client.get("/legacy/ticket/list/1").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
         assert_eq!(
-            response.body_string(),
-            Some(json!({"code": 422, "info": "Ticket id: 1 doesn't exist"}).to_string())
+            body_of(&mut response),
+            json!({
+                "type": "/problems/ticket-not-found",
+                "title": "Ticket not found",
+                "status": 404,
+                "detail": "Ticket id: 1 doesn't exist",
+                "instance": "/legacy/ticket/list/1",
+            })
         );
     }
 
     #[test]
-    fn Route_append_to_ticket_failure() {
+    fn Route_legacy_append_to_ticket_failure() {
         let client = Client::new(rocket()).expect("Valid rocket instance");
-        let mut response = client.get("/ticket/append/1?lines=10").dispatch();
-        assert_eq!(response.status(), Status::UnprocessableEntity);
+        let mut response = client.get("/legacy/ticket/append/1?lines=10").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
         assert_eq!(
-            response.body_string(),
-            Some(json!({"code": 422, "info": "Ticket id: 1 doesn't exist"}).to_string())
+            body_of(&mut response),
+            json!({
+                "type": "/problems/ticket-not-found",
+                "title": "Ticket not found",
+                "status": 404,
+                "detail": "Ticket id: 1 doesn't exist",
+                "instance": "/legacy/ticket/append/1?lines=10",
+            })
         );
     }
 
     #[test]
-    fn Route_evaluate_ticket_failure() {
+    fn Route_legacy_evaluate_ticket_failure() {
         let client = Client::new(rocket()).expect("Valid rocket instance");
-        let mut response = client.get("/eval/1").dispatch();
+        let mut response = client.get("/legacy/eval/1").dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+        assert_eq!(
+            body_of(&mut response),
+            json!({
+                "type": "/problems/ticket-not-found",
+                "title": "Ticket not found",
+                "status": 404,
+                "detail": "Ticket id: 1 doesn't exist",
+                "instance": "/legacy/eval/1",
+            })
+        );
+    }
+
+    // The development profile of Rocket.toml, with an admin token configured
+    fn admin_rocket() -> rocket::Rocket {
+        let mut config = rocket::ignite().config().clone();
+        config
+            .extras
+            .insert(String::from("admin_token"), "test-admin-token".into());
+        super::assemble(rocket::custom(config))
+    }
+
+    const ADMIN: &str = "Bearer test-admin-token";
+
+    #[test]
+    fn Route_admin_unauthorized() {
+        use rocket::http::Header;
+        let client = Client::new(admin_rocket()).expect("Valid rocket instance");
+        let mut response = client.get("/admin/export").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        assert_eq!(
+            body_of(&mut response)["type"],
+            json!("/problems/unauthorized")
+        );
+
+        let response = client
+            .get("/admin/export")
+            .header(Header::new("Authorization", "Bearer wrong"))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+        let response = client
+            .get("/admin/export")
+            .header(Header::new("Authorization", format!("x{}", ADMIN)))
+            .dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+
+        // Without a token the admin routes aren't there at all
+        let client = Client::new(rocket()).expect("Valid rocket instance");
+        let response = client
+            .get("/admin/export")
+            .header(Header::new("Authorization", ADMIN))
+            .dispatch();
+        assert_eq!(response.status(), Status::NotFound);
+    }
+
+    #[test]
+    fn Route_admin_export_import() {
+        use rocket::http::Header;
+        let source = Client::new(admin_rocket()).expect("Valid rocket instance");
+        source.post("/ticket").dispatch();
+        source.put("/status/1").dispatch();
+        let mut response = source
+            .get("/admin/export?format=ndjson")
+            .header(Header::new("Authorization", ADMIN))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        let export = response.body_string().expect("Response body");
+        assert_eq!(export.lines().count(), 2);
+
+        let target = Client::new(admin_rocket()).expect("Valid rocket instance");
+        target.post("/ticket").dispatch();
+        let mut response = target
+            .post("/admin/import?mode=merge")
+            .header(Header::new("Authorization", ADMIN))
+            .header(ContentType::new("application", "x-ndjson"))
+            .body(&export)
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(
+            body_of(&mut response)["data"],
+            json!({"mode": "merge", "imported": 0, "collisions": [1], "last_id": 1})
+        );
+
+        let mut response = target
+            .post("/admin/import?mode=replace")
+            .header(Header::new("Authorization", ADMIN))
+            .header(ContentType::new("application", "x-ndjson"))
+            .body(&export)
+            .dispatch();
+        assert_eq!(body_of(&mut response)["data"]["imported"], json!(1));
+        let mut response = target.get("/ticket/1").dispatch();
+        assert_eq!(body_of(&mut response)["data"]["state"], json!("checked"));
+    }
+
+    #[test]
+    fn Route_admin_rebuild() {
+        let client = Client::new(admin_rocket()).expect("Valid rocket instance");
+        client.post("/ticket").dispatch();
+        client.post("/ticket").dispatch();
+        client.delete("/ticket/2").dispatch();
+        let before = body_of(&mut client.get("/ticket/2").dispatch());
+
+        let mut response = client
+            .post("/admin/rebuild")
+            .header(Header::new("Authorization", ADMIN))
+            .dispatch();
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(body_of(&mut response)["data"]["tickets"], json!(2));
+        assert_eq!(body_of(&mut client.get("/ticket/2").dispatch()), before);
+
+        let response = client.post("/admin/rebuild").dispatch();
+        assert_eq!(response.status(), Status::Unauthorized);
+    }
+
+    #[test]
+    fn Route_admin_import_invalid() {
+        use rocket::http::Header;
+        let client = Client::new(admin_rocket()).expect("Valid rocket instance");
+        let export = json!({
+            "version": 1,
+            "exported_at": 0,
+            "last_id": 1,
+            "ticket_count": 1,
+            "tickets": [{
+                "id": 1,
+                "line_list": [[0, 3, 1]],
+                "state": "open",
+                "transitions": [{"from": null, "to": "open", "at": 0}],
+                "result": null
+            }]
+        });
+        let mut response = client
+            .post("/admin/import")
+            .header(Header::new("Authorization", ADMIN))
+            .header(ContentType::JSON)
+            .body(export.to_string())
+            .dispatch();
         assert_eq!(response.status(), Status::UnprocessableEntity);
         assert_eq!(
-            response.body_string(),
-            Some(json!({"code": 422, "info": "Ticket id: 1 doesn't exist"}).to_string())
+            body_of(&mut response)["type"],
+            json!("/problems/invalid-import")
         );
     }
 }
@@ -0,0 +1,154 @@
+use {
+    super::{Fail, Good, Internal, Response},
+    crate::{
+        models::{ImportMode, ImportReport},
+        transfer::{self, Format},
+    },
+    rocket::{
+        http::{ContentType, Status},
+        request::{self, FromRequest, Request},
+        response::content::Content,
+        Data, Outcome, State,
+    },
+    serde::Serialize,
+    std::io::Read,
+};
+
+// Where the admin routes are mounted, only if an admin token is configured
+pub(super) const BASE: &str = "/admin";
+
+// Largest import accepted, in bytes
+const IMPORT_LIMIT: u64 = 64 * 1024 * 1024;
+
+// Secret that admin requests must present as "Authorization: Bearer <token>"
+pub(super) struct AdminToken(pub(super) String);
+
+// What the Authorization header starts with, ahead of the token
+const BEARER: &str = "Bearer ";
+
+// Request guard which only lets requests carrying the admin token through
+pub(super) struct Admin;
+
+impl<'a, 'r> FromRequest<'a, 'r> for Admin {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let token = match request.guard::<State<AdminToken>>() {
+            Outcome::Success(token) => token,
+            _ => return Outcome::Failure((Status::InternalServerError, ())),
+        };
+        let presented = request
+            .headers()
+            .get_one("Authorization")
+            .filter(|header| header.starts_with(BEARER))
+            .map(|header| &header[BEARER.len()..]);
+
+        match presented {
+            Some(presented) if matches(presented, &token.0) => Outcome::Success(Admin),
+            _ => Outcome::Failure((Status::Unauthorized, ())),
+        }
+    }
+}
+
+// Compares the whole of both tokens, so timing doesn't reveal how much of one matched
+fn matches(presented: &str, token: &str) -> bool {
+    presented.len() == token.len()
+        && presented
+            .bytes()
+            .zip(token.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// Exports the entire Raffle as a [format] (json or ndjson) document
+#[get("/export?<format>")]
+pub(super) fn export_raffle(
+    _admin: Admin,
+    state: Internal,
+    format: Option<String>,
+) -> Result<Content<String>, Fail> {
+    let format = match format.map(|format| format.parse::<Format>()) {
+        Some(Ok(format)) => format,
+        Some(Err(e)) => return Err(Fail::from(e)),
+        None => Format::Json,
+    };
+    match state.read() {
+        Ok(raffle) => match raffle
+            .export()
+            .and_then(|export| transfer::encode(&export, format))
+        {
+            Ok(text) => Ok(Content(content_type(format), text)),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Imports an exported Raffle, merging it into or replacing the current one per [mode].
+// The body is read as NDJSON if sent as application/x-ndjson, JSON otherwise
+#[post("/import?<mode>", data = "<body>")]
+pub(super) fn import_raffle(
+    _admin: Admin,
+    state: Internal,
+    mode: Option<String>,
+    kind: Option<&ContentType>,
+    body: Data,
+) -> Response<ImportReport> {
+    let mode = match mode.map(|mode| mode.parse::<ImportMode>()) {
+        Some(Ok(mode)) => mode,
+        Some(Err(e)) => return Err(Fail::from(e)),
+        None => ImportMode::Merge,
+    };
+    let format = match kind {
+        Some(kind) if *kind == content_type(Format::Ndjson) => Format::Ndjson,
+        _ => Format::Json,
+    };
+
+    let mut text = String::new();
+    if let Err(e) = body.open().take(IMPORT_LIMIT + 1).read_to_string(&mut text) {
+        return Err(Fail::BadRequest(format!("unreadable body: {}", e)));
+    }
+    if text.len() as u64 > IMPORT_LIMIT {
+        return Err(Fail::BadRequest(format!(
+            "import is larger than {} bytes",
+            IMPORT_LIMIT
+        )));
+    }
+
+    let export = match transfer::decode(&text, format) {
+        Ok(export) => export,
+        Err(e) => return Err(Fail::from(e)),
+    };
+    match state.write() {
+        Ok(mut raffle) => match raffle.import(export, mode) {
+            Ok(report) => Ok(Good::Success(report)),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Derives every Ticket again from the recorded Events, for when the stored
+// Tickets are suspected to have drifted from them
+#[post("/rebuild")]
+pub(super) fn rebuild_raffle(_admin: Admin, state: Internal) -> Response<Rebuilt> {
+    match state.write() {
+        Ok(mut raffle) => match raffle.rebuild() {
+            Ok(tickets) => Ok(Good::Success(Rebuilt { tickets })),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+#[derive(Serialize)]
+pub(super) struct Rebuilt {
+    tickets: u64,
+}
+
+fn content_type(format: Format) -> ContentType {
+    match format {
+        Format::Json => ContentType::JSON,
+        Format::Ndjson => ContentType::new("application", "x-ndjson"),
+    }
+}
@@ -0,0 +1,130 @@
+use {
+    super::{Fail, Internal, Requester},
+    rocket::{
+        http::{ContentType, Status},
+        request::Request,
+        response::{self, Responder, Response as rResponse},
+    },
+    serde_json::{json, value::Value as jVal},
+    std::io::Cursor,
+};
+
+// Where the pre-REST routes are mounted
+pub(super) const BASE: &str = "/legacy";
+
+// Legacy routes keep the original, unversioned response bodies
+type Response = Result<Legacy, Fail>;
+
+// Creates a new ticket with the default number of Lines [10]
+#[get("/ticket")]
+pub(super) fn create_ticket(state: Internal, requester: Requester) -> Response {
+    match state.write() {
+        Ok(mut raffle) => match raffle.new_ticket(None, None, requester.name()) {
+            Ok(ticket_id) => Ok(Legacy::Info(format!(
+                "Added ticket <{}> with [10] lines",
+                ticket_id
+            ))),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Creates a new ticket with a user defined number of Lines [lines]
+#[get("/ticket/<lines>")]
+pub(super) fn create_ticket_with(state: Internal, requester: Requester, lines: u64) -> Response {
+    match state.write() {
+        Ok(mut raffle) => match raffle.new_ticket(Some(lines), None, requester.name()) {
+            Ok(ticket_id) => Ok(Legacy::Info(format!(
+                "Added ticket <{}> with [{}] lines",
+                ticket_id, lines
+            ))),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Returns the entire list of Tickets as Json
+#[get("/ticket/list")]
+pub(super) fn get_ticket_list(state: Internal) -> Response {
+    match state.read() {
+        Ok(raffle) => match raffle.get_ticket_list(false) {
+            Ok(tickets) => Ok(Legacy::Success(json!(tickets))),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Returns a user defined Ticket via its id [id]
+#[get("/ticket/list/<id>")]
+pub(super) fn get_ticket_from(state: Internal, id: String) -> Response {
+    match state.read() {
+        Ok(raffle) => match raffle.get_ticket(&id) {
+            Ok(ticket) => Ok(Legacy::Success(json!(ticket))),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Appends a user defined number of Lines [lines] to a Ticket via its id [id]
+#[get("/ticket/append/<id>?<lines>")]
+pub(super) fn append_to_ticket(
+    state: Internal,
+    requester: Requester,
+    id: String,
+    lines: Option<u64>,
+) -> Response {
+    match (lines, state.write()) {
+        (Some(lines), Ok(mut raffle)) => match raffle.append_ticket(&id, lines, requester.name()) {
+            Ok(_) => Ok(Legacy::Info(format!(
+                "Appended [{}] lines to ticket <{}>",
+                lines, id
+            ))),
+            Err(e) => Err(Fail::from(e)),
+        },
+        (None, _) => Err(Fail::BadRequest(String::from(
+            "malformed query: [lines={unsigned integer}]",
+        ))),
+        (_, Err(e)) => Err(Fail::from(e)),
+    }
+}
+
+// Checks a Ticket via its id [id] and returns its score
+#[get("/eval/<id>")]
+pub(super) fn evaluate_ticket(state: Internal, id: String) -> Response {
+    match state.write() {
+        Ok(mut raffle) => match raffle.evaluate_ticket(&id) {
+            Ok(result) => Ok(Legacy::Success(json!(result))),
+            Err(e) => Err(Fail::from(e)),
+        },
+        Err(e) => Err(Fail::from(e)),
+    }
+}
+
+// Successful responses, as sent before the API was versioned
+#[derive(Debug)]
+pub(super) enum Legacy {
+    Info(String),
+    Success(jVal),
+}
+
+// Custom implementation for legacy API responses
+impl<'r> Responder<'r> for Legacy {
+    fn respond_to(self, _: &Request) -> response::Result<'r> {
+        match self {
+            Legacy::Info(i) => rResponse::build()
+                .sized_body(Cursor::new(json!({"code": 200, "info": i}).to_string()))
+                .header(ContentType::JSON)
+                .status(Status::Ok)
+                .ok(),
+            Legacy::Success(value) => rResponse::build()
+                .sized_body(Cursor::new(json!({"code": 200, "data": value}).to_string()))
+                .header(ContentType::JSON)
+                .status(Status::Ok)
+                .ok(),
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use {
+    crate::models::{ErrorKind, Raffle},
+    std::{
+        ops::{Deref, DerefMut},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard,
+        },
+        thread,
+    },
+};
+
+// The Raffle as shared between request handlers and background threads. A panic
+// while it is being changed doesn't leave it unusable: whoever takes it next first
+// rolls it back to the last consistent state, everything its Events account for
+pub struct SharedRaffle {
+    raffle: RwLock<Raffle>,
+    // Set when a writer panicked, until the Raffle is recovered
+    poisoned: AtomicBool,
+}
+
+impl SharedRaffle {
+    pub fn new(raffle: Raffle) -> Self {
+        SharedRaffle {
+            raffle: RwLock::new(raffle),
+            poisoned: AtomicBool::new(false),
+        }
+    }
+
+    // Locks the Raffle for reading, recovering it first if a writer panicked.
+    // Only fails if it couldn't be recovered
+    pub fn read(&self) -> Result<RwLockReadGuard<'_, Raffle>, ErrorKind> {
+        if self.poisoned.load(Ordering::SeqCst) {
+            drop(self.write()?);
+        }
+        Ok(self.raffle.read().unwrap_or_else(PoisonError::into_inner))
+    }
+
+    // Locks the Raffle for writing, recovering it first if a writer panicked.
+    // Only fails if it couldn't be recovered
+    pub fn write(&self) -> Result<WriteGuard<'_>, ErrorKind> {
+        let mut raffle = self.raffle.write().unwrap_or_else(PoisonError::into_inner);
+        if self.poisoned.load(Ordering::SeqCst) {
+            self.recover(&mut raffle)?;
+        }
+        Ok(WriteGuard {
+            raffle,
+            poisoned: &self.poisoned,
+        })
+    }
+
+    // Internal function for throwing away whatever a panicking writer left half done,
+    // by deriving every Ticket again from the committed Events
+    fn recover(&self, raffle: &mut Raffle) -> Result<(), ErrorKind> {
+        let tickets = match raffle.rebuild() {
+            Ok(tickets) => tickets,
+            Err(e) => {
+                eprintln!("Unable to recover raffle after a panic: {}", e);
+                return Err(e);
+            }
+        };
+        self.poisoned.store(false, Ordering::SeqCst);
+        eprintln!(
+            "Recovered raffle after a panic, rebuilt {} tickets",
+            tickets
+        );
+        Ok(())
+    }
+}
+
+// Write access to the Raffle, which marks it for recovery if dropped by a panic
+pub struct WriteGuard<'a> {
+    raffle: RwLockWriteGuard<'a, Raffle>,
+    poisoned: &'a AtomicBool,
+}
+
+impl<'a> Deref for WriteGuard<'a> {
+    type Target = Raffle;
+
+    fn deref(&self) -> &Raffle {
+        &self.raffle
+    }
+}
+
+impl<'a> DerefMut for WriteGuard<'a> {
+    fn deref_mut(&mut self) -> &mut Raffle {
+        &mut self.raffle
+    }
+}
+
+impl<'a> Drop for WriteGuard<'a> {
+    fn drop(&mut self) {
+        // Runs before the lock is released, so nobody sees the Raffle unmarked
+        if thread::panicking() {
+            self.poisoned.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn SharedRaffle_recover() {
+        let shared = SharedRaffle::new(Raffle::instantiate());
+        shared
+            .write()
+            .unwrap()
+            .new_ticket(Some(2), None, None)
+            .unwrap();
+        let before = shared.read().unwrap().snapshot().unwrap();
+
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut raffle = shared.write().unwrap();
+            raffle.append_ticket("1", 3, None).unwrap();
+            panic!("handler bug");
+        }));
+        assert!(outcome.is_err());
+        assert!(shared.poisoned.load(Ordering::SeqCst));
+
+        // The amendment was committed before the panic, so it survives
+        let raffle = shared.read().unwrap();
+        assert_eq!(raffle.get_ticket("1").unwrap().line_count, 5);
+        assert_ne!(raffle.snapshot().unwrap(), before);
+        drop(raffle);
+        assert!(!shared.poisoned.load(Ordering::SeqCst));
+
+        shared
+            .write()
+            .unwrap()
+            .new_ticket(None, None, None)
+            .unwrap();
+        assert_eq!(
+            shared.read().unwrap().get_ticket_list(false).unwrap().len(),
+            2
+        );
+        assert!(!shared.poisoned.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn SharedRaffle_recover_read_panic() {
+        let shared = SharedRaffle::new(Raffle::instantiate());
+        let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _raffle = shared.read().unwrap();
+            panic!("handler bug");
+        }));
+        assert!(outcome.is_err());
+
+        // Readers can't have changed anything, so there is nothing to recover
+        assert!(!shared.poisoned.load(Ordering::SeqCst));
+        assert!(shared.write().is_ok());
+    }
+}
@@ -0,0 +1,27 @@
+use {
+    crate::{
+        models::{ErrorKind, SweepReport},
+        shared::SharedRaffle,
+    },
+    std::{sync::Arc, thread, time::Duration},
+};
+
+// Sweeps the Raffle every [interval] in the background, for as long as the process
+// runs, reporting every sweep that changed something
+pub fn spawn_sweeper(raffle: Arc<SharedRaffle>, interval: Duration) {
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        match sweep(&raffle) {
+            Ok(report) if report.total() > 0 => {
+                println!("Swept {} tickets: {}", report.total(), report)
+            }
+            Ok(_) => (),
+            Err(e) => eprintln!("Failed to sweep raffle: {}", e),
+        }
+    });
+}
+
+// Expires, checks and removes whatever Tickets the Raffle's expiry policy says are due
+pub fn sweep(raffle: &SharedRaffle) -> Result<SweepReport, ErrorKind> {
+    raffle.write()?.sweep()
+}
@@ -0,0 +1,160 @@
+use {
+    crate::models::{ErrorKind, Export, ExportedTicket, RulesetVersion},
+    serde::{Deserialize, Serialize},
+    std::str::FromStr,
+};
+
+// How an Export is laid out as text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    // A single JSON document
+    Json,
+    // A header line followed by one line per Ticket, for exports too big to hold at once
+    Ndjson,
+}
+
+impl FromStr for Format {
+    type Err = ErrorKind;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Format::Json),
+            "ndjson" => Ok(Format::Ndjson),
+            other => Err(ErrorKind::InvalidQuery(format!(
+                "unknown format '{}', expected one of: json, ndjson",
+                other
+            ))),
+        }
+    }
+}
+
+// First line of an NDJSON export, every other field of the Export
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    version: u32,
+    exported_at: u64,
+    last_id: u64,
+    ticket_count: u64,
+    #[serde(default)]
+    rulesets: Vec<RulesetVersion>,
+}
+
+// Writes [export] out as [format]
+pub fn encode(export: &Export, format: Format) -> Result<String, ErrorKind> {
+    match format {
+        Format::Json => serde_json::to_string_pretty(export).map_err(encoding),
+        Format::Ndjson => {
+            let header = Header {
+                version: export.version,
+                exported_at: export.exported_at,
+                last_id: export.last_id,
+                ticket_count: export.ticket_count,
+                rulesets: export.rulesets.clone(),
+            };
+            let mut text = serde_json::to_string(&header).map_err(encoding)?;
+            text.push('\n');
+            for ticket in &export.tickets {
+                text.push_str(&serde_json::to_string(ticket).map_err(encoding)?);
+                text.push('\n');
+            }
+
+            Ok(text)
+        }
+    }
+}
+
+// Reads an Export written as [format], or returns an error saying where it is malformed
+pub fn decode(text: &str, format: Format) -> Result<Export, ErrorKind> {
+    match format {
+        Format::Json => serde_json::from_str(text).map_err(|e| malformed(format!("{}", e))),
+        Format::Ndjson => {
+            let mut lines = text
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| !line.trim().is_empty());
+            let header: Header = match lines.next() {
+                Some((_, line)) => serde_json::from_str(line)
+                    .map_err(|e| malformed(format!("header line: {}", e)))?,
+                None => return Err(malformed(String::from("the export is empty"))),
+            };
+            let tickets = lines
+                .map(|(number, line)| {
+                    serde_json::from_str::<ExportedTicket>(line)
+                        .map_err(|e| malformed(format!("line {}: {}", number + 1, e)))
+                })
+                .collect::<Result<Vec<ExportedTicket>, ErrorKind>>()?;
+
+            Ok(Export {
+                version: header.version,
+                exported_at: header.exported_at,
+                last_id: header.last_id,
+                ticket_count: header.ticket_count,
+                tickets,
+                rulesets: header.rulesets,
+            })
+        }
+    }
+}
+
+fn malformed(problem: String) -> ErrorKind {
+    ErrorKind::InvalidImport(vec![problem])
+}
+
+fn encoding(e: serde_json::Error) -> ErrorKind {
+    ErrorKind::Storage(format!("unable to encode export: {}", e))
+}
+
+/*
+Code
+-------------------------------------------------------------------------------
+Tests
+*/
+
+#[cfg(test)]
+mod tests {
+    #![allow(non_snake_case)]
+    use {super::*, crate::models::Raffle};
+
+    fn sample() -> Export {
+        let mut raffle = Raffle::instantiate();
+        raffle.new_ticket(Some(2), None, None).unwrap();
+        raffle.new_ticket(Some(3), None, None).unwrap();
+        raffle.evaluate_ticket("2").unwrap();
+        raffle.export().unwrap()
+    }
+
+    #[test]
+    fn transfer_json() {
+        let export = sample();
+        let text = encode(&export, Format::Json).unwrap();
+        assert_eq!(decode(&text, Format::Json).unwrap(), export);
+    }
+
+    #[test]
+    fn transfer_ndjson() {
+        let export = sample();
+        let text = encode(&export, Format::Ndjson).unwrap();
+        assert_eq!(text.lines().count(), 3);
+        assert_eq!(decode(&text, Format::Ndjson).unwrap(), export);
+    }
+
+    #[test]
+    fn transfer_malformed() {
+        let text = encode(&sample(), Format::Ndjson).unwrap();
+        let broken = text.replacen("\"state\"", "\"stat", 1);
+
+        match decode(&broken, Format::Ndjson) {
+            Err(ErrorKind::InvalidImport(problems)) => assert!(problems[0].starts_with("line 2")),
+            other => panic!("expected InvalidImport, got {:?}", other),
+        }
+        assert!(decode("", Format::Ndjson).is_err());
+        assert!(decode("[]", Format::Json).is_err());
+    }
+
+    #[test]
+    fn Format_from_str() {
+        assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+        assert_eq!("ndjson".parse::<Format>().unwrap(), Format::Ndjson);
+        assert!("xml".parse::<Format>().is_err());
+    }
+}